@@ -0,0 +1,434 @@
+//! Abstract interpretation of ElfCode programs, tracking each register as a constant, an
+//! interval or unknown.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::cfg::{Cfg, EdgeKind};
+use crate::disassembler::{condition, flow, Flow};
+use crate::{OpKind, Operand, Program, REGISTER_COUNT};
+
+/// What is known about a register at some point of the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbstractValue {
+    Const(usize),
+    /// Somewhere in `lo..=hi`.
+    Interval(usize, usize),
+    Unknown,
+}
+
+impl AbstractValue {
+    fn range(lo: usize, hi: usize) -> Self {
+        match (lo, hi) {
+            (lo, hi) if lo == hi => AbstractValue::Const(lo),
+            (0, usize::MAX) => AbstractValue::Unknown,
+            (lo, hi) => AbstractValue::Interval(lo, hi),
+        }
+    }
+
+    pub fn bounds(self) -> (usize, usize) {
+        match self {
+            AbstractValue::Const(v) => (v, v),
+            AbstractValue::Interval(lo, hi) => (lo, hi),
+            AbstractValue::Unknown => (0, usize::MAX),
+        }
+    }
+
+    /// The smallest value covering both.
+    fn join(self, other: Self) -> Self {
+        let ((a_lo, a_hi), (b_lo, b_hi)) = (self.bounds(), other.bounds());
+        Self::range(a_lo.min(b_lo), a_hi.max(b_hi))
+    }
+
+    /// Like [`Self::join`], but bounds that moved go straight to their extreme so loops
+    /// converge.
+    fn widen(self, next: Self) -> Self {
+        let ((old_lo, old_hi), (new_lo, new_hi)) = (self.bounds(), next.bounds());
+        let lo = if new_lo < old_lo { 0 } else { old_lo };
+        let hi = if new_hi > old_hi { usize::MAX } else { old_hi };
+        Self::range(lo, hi)
+    }
+}
+
+impl fmt::Display for AbstractValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AbstractValue::Const(v) => write!(f, "{v}"),
+            AbstractValue::Interval(lo, hi) => write!(f, "{lo}..={hi}"),
+            AbstractValue::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// The smallest number of the form `2^k - 1` that is at least `value`.
+fn all_ones_above(value: usize) -> usize {
+    usize::MAX.checked_shr(value.leading_zeros()).unwrap_or(0)
+}
+
+fn evaluate(op: OpKind, a: AbstractValue, b: AbstractValue) -> AbstractValue {
+    let ((a_lo, a_hi), (b_lo, b_hi)) = (a.bounds(), b.bounds());
+    let flag = |always: bool, never: bool| match (always, never) {
+        (true, _) => AbstractValue::Const(1),
+        (_, true) => AbstractValue::Const(0),
+        _ => AbstractValue::Interval(0, 1),
+    };
+
+    // Results that would overflow make the machine fault, so saturating is sound.
+    match op {
+        OpKind::Addr | OpKind::Addi => {
+            AbstractValue::range(a_lo.saturating_add(b_lo), a_hi.saturating_add(b_hi))
+        }
+        OpKind::Mulr | OpKind::Muli => {
+            AbstractValue::range(a_lo.saturating_mul(b_lo), a_hi.saturating_mul(b_hi))
+        }
+        OpKind::Banr | OpKind::Bani => match (a, b) {
+            (AbstractValue::Const(x), AbstractValue::Const(y)) => AbstractValue::Const(x & y),
+            _ => AbstractValue::range(0, a_hi.min(b_hi)),
+        },
+        OpKind::Borr | OpKind::Bori => match (a, b) {
+            (AbstractValue::Const(x), AbstractValue::Const(y)) => AbstractValue::Const(x | y),
+            _ => AbstractValue::range(a_lo.max(b_lo), all_ones_above(a_hi.max(b_hi))),
+        },
+        OpKind::Setr | OpKind::Seti => a,
+        OpKind::Gtir | OpKind::Gtri | OpKind::Gtrr => flag(a_lo > b_hi, a_hi <= b_lo),
+        OpKind::Eqir | OpKind::Eqri | OpKind::Eqrr => {
+            flag(a_lo == a_hi && a == b, a_hi < b_lo || b_hi < a_lo)
+        }
+    }
+}
+
+/// Registers at the start of each instruction, `None` where it is unreachable.
+type States = Vec<Option<[AbstractValue; REGISTER_COUNT]>>;
+
+/// Joins along a back edge this many times before widening.
+const WIDEN_AFTER: usize = 3;
+
+/// Jumps to an interval of targets wider than this are not followed.
+const MAX_TARGETS: usize = 2;
+
+/// A fact derived by [`Analysis::facts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fact {
+    /// `register` always holds `value` when the loop header at `address` is reached.
+    Constant {
+        address: usize,
+        register: usize,
+        value: usize,
+    },
+    /// The loop spanning `start..=end` is left when `condition` holds.
+    LoopExit {
+        start: usize,
+        end: usize,
+        condition: String,
+    },
+}
+
+impl fmt::Display for Fact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fact::Constant {
+                address,
+                register,
+                value,
+            } => write!(f, "r{register} is constant {value} at ip {address}"),
+            Fact::LoopExit {
+                start,
+                end,
+                condition,
+            } => write!(f, "loop at {start}..={end} exits when {condition}"),
+        }
+    }
+}
+
+/// The result of abstractly executing a program from a given set of initial registers.
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub ip_register: usize,
+    pub states: States,
+    /// Instructions that may move the ip outside of the program.
+    pub halting: Vec<usize>,
+    /// Instructions whose possible targets were too many to follow.
+    pub unresolved: Vec<usize>,
+}
+
+impl Analysis {
+    pub fn new(
+        ip_register: usize,
+        program: &Program,
+        initial: [AbstractValue; REGISTER_COUNT],
+    ) -> Self {
+        let mut states: States = vec![None; program.len()];
+        let mut joins = vec![0; program.len()];
+        let mut halting = BTreeSet::new();
+        let mut unresolved = BTreeSet::new();
+        let mut to_visit = BTreeSet::new();
+
+        if !program.is_empty() {
+            states[0] = Some(initial);
+            to_visit.insert(0);
+        }
+
+        while let Some(address) = to_visit.pop_first() {
+            let Some(mut registers) = states[address] else {
+                continue;
+            };
+            let ins = &program[address];
+
+            if ins.output >= REGISTER_COUNT {
+                continue;
+            }
+
+            registers[ip_register] = AbstractValue::Const(address);
+            let value = |operand: Operand, raw: usize| match operand {
+                Operand::Register => registers
+                    .get(raw)
+                    .copied()
+                    .unwrap_or(AbstractValue::Unknown),
+                Operand::Immediate => AbstractValue::Const(raw),
+                Operand::Ignored => AbstractValue::Const(0),
+            };
+            let a = value(ins.op.operand_a(), ins.input_a);
+            let b = value(ins.op.operand_b(), ins.input_b);
+            registers[ins.output] = evaluate(ins.op, a, b);
+
+            let (lo, hi) = registers[ip_register].bounds();
+            if hi - lo >= MAX_TARGETS {
+                unresolved.insert(address);
+                continue;
+            }
+
+            // `addr flag ip ip` tells which value the flag had on each path.
+            let flag = match (ins.input_a == ip_register, ins.input_b == ip_register) {
+                _ if ins.op != OpKind::Addr || ins.output != ip_register => None,
+                (true, false) => Some(ins.input_b),
+                (false, true) => Some(ins.input_a),
+                _ => None,
+            };
+
+            for value in lo..=hi {
+                let Some(target) = value.checked_add(1).filter(|&t| t < program.len()) else {
+                    halting.insert(address);
+                    continue;
+                };
+
+                let mut incoming = registers;
+                incoming[ip_register] = AbstractValue::Const(value);
+                if let Some(flag) = flag {
+                    incoming[flag] = AbstractValue::Const(value - address);
+                }
+
+                let merged = match states[target] {
+                    None => incoming,
+                    Some(old) => {
+                        // Only loops can grow a value forever, so only back edges widen.
+                        let widen = target <= address && {
+                            joins[target] += 1;
+                            joins[target] > WIDEN_AFTER
+                        };
+                        let mut merged = old;
+                        for (m, new) in merged.iter_mut().zip(incoming) {
+                            *m = if widen { m.widen(new) } else { m.join(new) };
+                        }
+                        merged
+                    }
+                };
+
+                if states[target] != Some(merged) {
+                    states[target] = Some(merged);
+                    to_visit.insert(target);
+                }
+            }
+        }
+
+        Self {
+            ip_register,
+            states,
+            halting: halting.into_iter().collect(),
+            unresolved: unresolved.into_iter().collect(),
+        }
+    }
+
+    /// What is known about `register` just before the instruction at `address` runs.
+    pub fn value_at(&self, address: usize, register: usize) -> Option<AbstractValue> {
+        if register == self.ip_register {
+            return Some(AbstractValue::Const(address));
+        }
+        self.states
+            .get(address)?
+            .map(|registers| registers[register])
+    }
+
+    /// Constant registers at reachable loop headers, and the conditions under which each loop
+    /// is left through a compare-then-jump.
+    pub fn facts(&self, program: &Program) -> Vec<Fact> {
+        let cfg = Cfg::new(self.ip_register, program);
+        let mut facts = Vec::new();
+
+        for l in cfg.loops() {
+            let start = cfg.blocks[l.header].start;
+            let end = l
+                .body
+                .iter()
+                .map(|&b| cfg.blocks[b].last())
+                .max()
+                .unwrap_or(start);
+
+            if let Some(registers) = self.states[start] {
+                for (register, value) in registers.into_iter().enumerate() {
+                    if let AbstractValue::Const(value) = value {
+                        if register != self.ip_register {
+                            facts.push(Fact::Constant {
+                                address: start,
+                                register,
+                                value,
+                            });
+                        }
+                    }
+                }
+            }
+
+            // Exits to another block, then branches that halt the program.
+            let exits = l
+                .exits
+                .iter()
+                .filter_map(|edge| match edge.kind {
+                    EdgeKind::Taken => Some((edge.from, true)),
+                    EdgeKind::NotTaken => Some((edge.from, false)),
+                    _ => None,
+                })
+                .chain(
+                    cfg.halting
+                        .iter()
+                        .filter(|b| l.body.contains(b))
+                        .flat_map(|&b| [(b, true), (b, false)]),
+                );
+
+            for (block, holds) in exits {
+                let jump = cfg.blocks[block].last();
+                let Flow::Branch {
+                    compare,
+                    taken,
+                    fallthrough,
+                } = flow(self.ip_register, program, jump)
+                else {
+                    continue;
+                };
+
+                let target = if holds { taken } else { fallthrough };
+                if cfg.halting.contains(&block) && target < program.len() {
+                    continue;
+                }
+
+                facts.push(Fact::LoopExit {
+                    start,
+                    end,
+                    condition: condition(self.ip_register, compare, &program[compare], holds),
+                });
+            }
+        }
+
+        facts
+    }
+}
+
+/// Finds a compare of some register against r0 that decides whether the program halts,
+/// returning its address and the other register.
+pub fn halting_compare(ip_register: usize, program: &Program) -> Option<(usize, usize)> {
+    (0..program.len()).find_map(|address| {
+        let Flow::Branch {
+            compare,
+            taken,
+            fallthrough,
+        } = flow(ip_register, program, address)
+        else {
+            return None;
+        };
+
+        let ins = &program[compare];
+        let other = match (ins.input_a, ins.input_b) {
+            (0, other) | (other, 0) if other != 0 => other,
+            _ => return None,
+        };
+
+        let halts = taken.max(fallthrough) >= program.len();
+        (ins.op == OpKind::Eqrr && halts && ip_register != 0 && other != ip_register)
+            .then_some((compare, other))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{read_file_part, Day};
+    use crate::{parse_computer_program, Instruction};
+
+    fn load(day: u8) -> (usize, Program) {
+        parse_computer_program(&read_file_part("examples", Day::new(day).unwrap(), 2)).unwrap()
+    }
+
+    fn facts(day: u8, r0: AbstractValue) -> Vec<String> {
+        let (ip_register, program) = load(day);
+        let mut initial = [AbstractValue::Const(0); REGISTER_COUNT];
+        initial[0] = r0;
+
+        Analysis::new(ip_register, &program, initial)
+            .facts(&program)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn finds_divisor_sum_target() {
+        let facts = facts(19, AbstractValue::Const(1));
+
+        assert!(facts.contains(&"r5 is constant 15876 at ip 2".to_string()));
+        assert!(facts.contains(&"r5 is constant 15876 at ip 3".to_string()));
+        assert!(facts.contains(&"loop at 2..=15 exits when r2 > r5".to_string()));
+        assert!(facts.contains(&"loop at 3..=11 exits when r3 > r5".to_string()));
+    }
+
+    #[test]
+    fn finds_halting_condition() {
+        let facts = facts(21, AbstractValue::Unknown);
+
+        assert!(facts.contains(&"loop at 13..=20 exits when r1 > r4".to_string()));
+        assert!(facts.contains(&"loop at 1..=25 exits when r5 == r0".to_string()));
+    }
+
+    #[test]
+    fn tracks_intervals() {
+        let (ip_register, program) = load(21);
+        let analysis = Analysis::new(ip_register, &program, [AbstractValue::Const(0); 6]);
+
+        assert_eq!(
+            analysis.value_at(4, 3),
+            Some(AbstractValue::Interval(0, 15))
+        );
+        assert_eq!(
+            analysis.value_at(23, 5),
+            Some(AbstractValue::Interval(0, 65535))
+        );
+        assert_eq!(analysis.halting, vec![24]);
+        assert!(analysis.unresolved.is_empty());
+    }
+
+    #[test]
+    fn finds_halting_compare() {
+        let (ip_register, program) = load(21);
+        assert_eq!(halting_compare(ip_register, &program), Some((23, 5)));
+
+        // Swap r1 and r5, and drop the compare's operands into the other order.
+        let swap = |r| match r {
+            1 => 5,
+            5 => 1,
+            r => r,
+        };
+        let mut program: Program = program.iter().map(|i| i.map_registers(swap)).collect();
+        program[23] = Instruction::new(OpKind::Eqrr, 0, 1, 3);
+        assert_eq!(halting_compare(swap(ip_register), &program), Some((23, 1)));
+
+        let (ip_register, program) = load(19);
+        assert_eq!(halting_compare(ip_register, &program), None);
+    }
+}
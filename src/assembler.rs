@@ -0,0 +1,389 @@
+//! Assembles ElfCode with labels, register aliases and jump pseudo-ops into a [`Program`].
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{
+    decode_instruction, parse_computer_program, parse_number, parse_register, tokenize,
+    Instruction, OpKind, ParseError, ParseErrorKind, Program, REGISTER_COUNT,
+};
+
+/// Number of instructions each pseudo-op lowers to.
+fn pseudo_op_len(mnemonic: &str) -> Option<usize> {
+    match mnemonic {
+        "jmp" => Some(1),
+        "jz" => Some(2),
+        "jnz" => Some(3),
+        _ => None,
+    }
+}
+
+/// A source line split into its parts. Labels end in `:`, and a leading number is the address
+/// the instruction is expected at, as printed by the disassembler.
+struct Statement<'a> {
+    labels: Vec<(usize, &'a str)>,
+    address: Option<(usize, &'a str)>,
+    rest: &'a [(usize, &'a str)],
+}
+
+fn split_statement<'a>(tokens: &'a [(usize, &'a str)]) -> Statement<'a> {
+    let mut rest = tokens;
+    let mut labels = Vec::new();
+
+    while let Some((&(column, token), tail)) = rest.split_first() {
+        match token.strip_suffix(':') {
+            Some(label) => labels.push((column, label)),
+            None => break,
+        }
+        rest = tail;
+    }
+
+    let address = match rest.split_first() {
+        Some((&(column, token), tail)) if token.bytes().all(|b| b.is_ascii_digit()) => {
+            rest = tail;
+            Some((column, token))
+        }
+        _ => None,
+    };
+
+    Statement {
+        labels,
+        address,
+        rest,
+    }
+}
+
+/// Whether `token` is a register number, plain or written as `rN`.
+fn is_register_literal(token: &str) -> bool {
+    let number = token.strip_prefix('r').unwrap_or(token);
+    !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[derive(Default)]
+struct Symbols<'a> {
+    labels: HashMap<&'a str, usize>,
+    aliases: HashMap<&'a str, usize>,
+    ip_register: Option<usize>,
+}
+
+impl<'a> Symbols<'a> {
+    /// Resolves an alias, `rN` or a plain register number.
+    fn register(&self, line: usize, (column, token): (usize, &str)) -> Result<usize, ParseError> {
+        if let Some(&register) = self.aliases.get(token) {
+            return Ok(register);
+        }
+
+        let number = match token.strip_prefix('r') {
+            Some(number) if is_register_literal(token) => number,
+            _ => token,
+        };
+
+        parse_register(line, (column, number), REGISTER_COUNT)
+    }
+
+    fn define(
+        map: &mut HashMap<&'a str, usize>,
+        line: usize,
+        (column, name): (usize, &'a str),
+        value: usize,
+    ) -> Result<(), ParseError> {
+        match map.insert(name, value) {
+            None => Ok(()),
+            Some(_) => Err(ParseError {
+                line,
+                column,
+                kind: ParseErrorKind::Redefined(name.to_owned()),
+            }),
+        }
+    }
+
+    /// The value a jump has to write to the ip register to continue at `target`.
+    fn jump_value(
+        &self,
+        line: usize,
+        (column, target): (usize, &str),
+    ) -> Result<usize, ParseError> {
+        let error = |kind| ParseError { line, column, kind };
+
+        let address = match self.labels.get(target) {
+            Some(&address) => address,
+            None => target
+                .parse()
+                .map_err(|_| error(ParseErrorKind::UnknownLabel(target.to_owned())))?,
+        };
+
+        address
+            .checked_sub(1)
+            .ok_or(error(ParseErrorKind::JumpToEntry))
+    }
+
+    fn lower(
+        &self,
+        ip: usize,
+        line: usize,
+        tokens: &[(usize, &str)],
+    ) -> Result<Vec<Instruction>, ParseError> {
+        let (column, mnemonic) = tokens[0];
+        let operands = &tokens[1..];
+
+        let expected = match mnemonic {
+            "jmp" => 1,
+            "jz" | "jnz" => 2,
+            _ => {
+                let instruction =
+                    decode_instruction(line, tokens, |line, token| self.register(line, token))?;
+                return Ok(vec![instruction]);
+            }
+        };
+
+        if operands.len() != expected {
+            return Err(ParseError {
+                line,
+                column,
+                kind: ParseErrorKind::WrongOperandCount {
+                    expected,
+                    found: operands.len(),
+                },
+            });
+        }
+
+        let target = self.jump_value(line, operands[expected - 1])?;
+        let jump = Instruction::new(OpKind::Seti, target, 0, ip);
+
+        Ok(match mnemonic {
+            "jmp" => vec![jump],
+            // The flag is added to the ip, skipping the next instruction if it is 1.
+            "jz" => {
+                let flag = self.register(line, operands[0])?;
+                vec![Instruction::new(OpKind::Addr, flag, ip, ip), jump]
+            }
+            _ => {
+                let flag = self.register(line, operands[0])?;
+                vec![
+                    Instruction::new(OpKind::Addr, flag, ip, ip),
+                    Instruction::new(OpKind::Addi, ip, 1, ip),
+                    jump,
+                ]
+            }
+        })
+    }
+}
+
+/// Assembles `source` into the ip register and program.
+///
+/// On top of the puzzle input format this accepts:
+/// - `name:` labels, before an instruction or on their own line
+/// - `.ip N` as an alternative to `#ip N`. Either makes `ip` an alias for that register.
+/// - `.alias name register` to give a register a name, registers can also be written as `rN`.
+///   Names that read as a number or `rN` are rejected.
+/// - `jmp label`, continuing at `label`
+/// - `jz flag label` and `jnz flag label`, jumping if `flag` is 0 or 1 respectively. `flag` must
+///   hold 0 or 1, as left by a compare.
+/// - an address before an instruction, which must match its actual address
+///
+/// Jump targets may also be plain addresses. The listing produced by the disassembler assembles
+/// back into the original program.
+pub fn assemble(source: &str) -> Result<(usize, Program), ParseError> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, tokenize(line)))
+        .collect::<Vec<_>>();
+
+    let mut symbols = Symbols::default();
+    let mut address = 0;
+
+    for (line, tokens) in &lines {
+        let line = *line;
+        let statement = split_statement(tokens);
+
+        for &label in &statement.labels {
+            Symbols::define(&mut symbols.labels, line, label, address)?;
+        }
+
+        if let Some(token) = statement.address {
+            let found = parse_number(line, token)?;
+            if found != address {
+                return Err(ParseError {
+                    line,
+                    column: token.0,
+                    kind: ParseErrorKind::AddressMismatch {
+                        expected: address,
+                        found,
+                    },
+                });
+            }
+        }
+
+        match statement.rest {
+            [] => {}
+            [(column, ".ip" | "#ip"), register] => {
+                let register = symbols.register(line, *register)?;
+                if symbols.ip_register.replace(register).is_some() {
+                    return Err(ParseError {
+                        line,
+                        column: *column,
+                        kind: ParseErrorKind::Redefined("ip".to_owned()),
+                    });
+                }
+                Symbols::define(&mut symbols.aliases, line, (*column, "ip"), register)?;
+            }
+            [(_, ".alias"), (column, name), _] if is_register_literal(name) => {
+                return Err(ParseError {
+                    line,
+                    column: *column,
+                    kind: ParseErrorKind::InvalidAlias((*name).to_owned()),
+                });
+            }
+            [(_, ".alias"), name, register] => {
+                let register = symbols.register(line, *register)?;
+                Symbols::define(&mut symbols.aliases, line, *name, register)?;
+            }
+            [(column, directive @ (".ip" | "#ip" | ".alias")), rest @ ..] => {
+                return Err(ParseError {
+                    line,
+                    column: *column,
+                    kind: ParseErrorKind::WrongOperandCount {
+                        expected: if *directive == ".alias" { 2 } else { 1 },
+                        found: rest.len(),
+                    },
+                });
+            }
+            [(_, mnemonic), ..] => address += pseudo_op_len(mnemonic).unwrap_or(1),
+        }
+    }
+
+    let Some(ip_register) = symbols.ip_register else {
+        return Err(ParseError {
+            line: 1,
+            column: 1,
+            kind: ParseErrorKind::MissingIpDirective,
+        });
+    };
+
+    let mut program = Program::new();
+
+    for (line, tokens) in &lines {
+        let statement = split_statement(tokens);
+
+        match statement.rest.first() {
+            None | Some((_, ".ip" | "#ip" | ".alias")) => {}
+            Some(_) => program.extend(symbols.lower(ip_register, *line, statement.rest)?),
+        }
+    }
+
+    Ok((ip_register, program))
+}
+
+/// Renders a program in the puzzle input format.
+pub fn emit(ip_register: usize, program: &Program) -> String {
+    let mut output = format!("#ip {ip_register}\n");
+
+    for instruction in program {
+        let _ = writeln!(output, "{instruction}");
+    }
+
+    output
+}
+
+/// Re-emits a program in the puzzle input format with registers 1 to 5 renamed by `order`,
+/// leaving r0 in place. Solutions that discover their registers from the program give the same
+/// answers for any order.
+pub fn shuffle_registers(input: &str, order: [usize; 5]) -> Result<String, ParseError> {
+    let (ip_register, program) = parse_computer_program(input)?;
+    let rename = |r: usize| if r == 0 { 0 } else { order[r - 1] };
+
+    let program: Program = program.iter().map(|i| i.map_registers(rename)).collect();
+    Ok(emit(rename(ip_register), &program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::disassemble;
+    use crate::template::{read_file_part, Day};
+    use crate::{parse_computer_program, Computer};
+
+    #[test]
+    fn round_trips_through_the_disassembler() {
+        for day in [19, 21] {
+            let input = read_file_part("examples", Day::new(day).unwrap(), 2);
+            let (ip_register, program) = parse_computer_program(&input).unwrap();
+
+            let (ip_register, program) = assemble(&disassemble(ip_register, &program)).unwrap();
+
+            assert_eq!(emit(ip_register, &program), input);
+        }
+    }
+
+    #[test]
+    fn lowers_labels_aliases_and_jumps() {
+        let source = "\
+.ip 5
+.alias count r1
+.alias limit 2
+.alias flag r3
+
+        seti 4 0 limit
+loop:   addi count 1 count
+        gtrr limit count flag
+        jnz flag loop
+        jmp end
+        seti 99 0 count     ; skipped
+end:    eqrr count limit flag
+        jz flag loop
+";
+        let (ip_register, program) = assemble(source).unwrap();
+        let expected = parse_computer_program(
+            "#ip 5\nseti 4 0 2\naddi 1 1 1\ngtrr 2 1 3\naddr 3 5 5\naddi 5 1 5\nseti 0 0 5\nseti 7 0 5\nseti 99 0 1\neqrr 1 2 3\naddr 3 5 5\nseti 0 0 5",
+        )
+        .unwrap();
+        assert_eq!((ip_register, program.clone()), expected);
+
+        let mut computer = Computer::new(ip_register, program);
+        computer.run().unwrap();
+        assert_eq!(computer.registers[1], 4);
+    }
+
+    #[test]
+    fn reports_symbol_errors() {
+        let error = |source: &str| assemble(source).unwrap_err();
+
+        assert_eq!(
+            error(".ip 1\njmp nowhere"),
+            ParseError {
+                line: 2,
+                column: 5,
+                kind: ParseErrorKind::UnknownLabel("nowhere".to_owned())
+            }
+        );
+        assert_eq!(
+            error("#ip 1\nstart: seti 0 0 0\nstart: seti 0 0 0").kind,
+            ParseErrorKind::Redefined("start".to_owned())
+        );
+        assert_eq!(
+            error("#ip 1\nstart: seti 0 0 0\njmp start").kind,
+            ParseErrorKind::JumpToEntry
+        );
+        assert_eq!(
+            error("#ip 1\n0 seti 0 0 0\n2 seti 0 0 0").kind,
+            ParseErrorKind::AddressMismatch {
+                expected: 1,
+                found: 2
+            }
+        );
+        assert_eq!(error("seti 0 0 0").kind, ParseErrorKind::MissingIpDirective);
+        assert_eq!(
+            error("#ip 1\n.alias 3 2"),
+            ParseError {
+                line: 2,
+                column: 8,
+                kind: ParseErrorKind::InvalidAlias("3".to_owned())
+            }
+        );
+        assert_eq!(
+            error("#ip 1\n.alias r0 2").kind,
+            ParseErrorKind::InvalidAlias("r0".to_owned())
+        );
+    }
+}
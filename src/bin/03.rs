@@ -1,3 +1,5 @@
+use advent_of_code::grid::Bounds;
+use advent_of_code::Grid;
 use glam::IVec2;
 use itertools::Itertools;
 use std::cmp::{max, min};
@@ -19,6 +21,13 @@ impl Rectangle {
             height,
         }
     }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::new(
+            self.pos,
+            self.pos + IVec2::new(self.width - 1, self.height - 1),
+        )
+    }
 }
 
 fn parse_rectangle(line: &str) -> Option<Rectangle> {
@@ -59,20 +68,19 @@ fn overlap_rectangle(a: &Rectangle, b: &Rectangle) -> Option<Rectangle> {
 
 pub fn part_one(input: &str) -> Option<usize> {
     let rects = parse(input);
+    let mut overlapped_squares = Grid::sparse();
 
-    let overlapped_squares = rects
+    for overlap in rects
         .iter()
         .combinations(2)
         .filter_map(|combo| overlap_rectangle(combo[0], combo[1]))
-        .flat_map(|overlap| {
-            (overlap.pos.y..overlap.pos.y + overlap.height).flat_map(move |y| {
-                (overlap.pos.x..overlap.pos.x + overlap.width).map(move |x| IVec2::new(x, y))
-            })
-        })
-        .unique()
-        .count();
-
-    Some(overlapped_squares)
+    {
+        for pos in overlap.bounds().positions() {
+            overlapped_squares.insert(pos, ());
+        }
+    }
+
+    Some(overlapped_squares.len())
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
@@ -1,8 +1,7 @@
-use std::{
-    cmp::{max, min},
-    collections::{HashSet, VecDeque},
-};
+use std::collections::VecDeque;
 
+use advent_of_code::grid::{Bounds, Direction};
+use advent_of_code::Grid;
 use glam::IVec2;
 use itertools::Itertools;
 
@@ -38,107 +37,57 @@ fn find_closest_coord(coord: &IVec2, map: &[IVec2]) -> Option<IVec2> {
     Some(*closest[0].0)
 }
 
-fn is_in_bounds(coord: &IVec2, upper_left: &IVec2, lower_right: &IVec2) -> bool {
-    coord.x > upper_left.x
-        && coord.x < lower_right.x
-        && coord.y > upper_left.y
-        && coord.y < lower_right.y
+fn is_in_bounds(coord: &IVec2, bounds: &Bounds) -> bool {
+    bounds.contains(*coord) && !bounds.on_edge(*coord)
 }
 
-fn find_area(
-    coord: &IVec2,
-    map: &[IVec2],
-    upper_left: &IVec2,
-    lower_right: &IVec2,
-) -> Option<HashSet<IVec2>> {
-    let dirs = [
-        IVec2::new(0, -1),
-        IVec2::new(0, 1),
-        IVec2::new(-1, 0),
-        IVec2::new(1, 0),
-    ];
-
-    let mut area = HashSet::new();
+fn find_area(coord: &IVec2, map: &[IVec2], bounds: &Bounds) -> Option<Grid<()>> {
+    let mut area = Grid::sparse();
     let mut to_visit = VecDeque::from([*coord]);
 
     while let Some(current) = to_visit.pop_front() {
-        if !is_in_bounds(&current, upper_left, lower_right) {
+        if !is_in_bounds(&current, bounds) {
             return None;
         }
 
-        if !area.insert(current) {
+        if area.insert(current, ()).is_some() {
             continue;
         }
 
-        to_visit.extend(dirs.iter().map(|&dir| dir + current).filter(|&next_coord| {
-            find_closest_coord(&next_coord, map).map_or(false, |closest| closest == *coord)
+        to_visit.extend(Direction::neighbors(current).filter(|next_coord| {
+            find_closest_coord(next_coord, map).is_some_and(|closest| closest == *coord)
         }));
     }
 
     Some(area)
 }
 
-fn find_bounds(map: &[IVec2]) -> (IVec2, IVec2) {
-    let mut upper_left = IVec2::MAX;
-    let mut lower_right = IVec2::MIN;
-
-    for coord in map.iter() {
-        upper_left.x = min(upper_left.x, coord.x);
-        upper_left.y = min(upper_left.y, coord.y);
-
-        lower_right.x = max(lower_right.x, coord.x);
-        lower_right.y = max(lower_right.y, coord.y);
-    }
-
-    (upper_left, lower_right)
-}
-
-fn find_safe_region_size(
-    coords: &[IVec2],
-    upper_left: &IVec2,
-    lower_right: &IVec2,
-    constraint: usize,
-) -> usize {
-    (upper_left.y..=lower_right.y)
-        .flat_map(|y| {
-            (upper_left.x..=lower_right.x)
-                .map(move |x| IVec2::new(x, y))
-                .filter(|coord| {
-                    // Check if the total Manhattan distance to all points is less than 10000
-                    coords.iter().map(|c| manhattan(c, coord)).sum::<usize>() < constraint
-                })
+fn find_safe_region_size(coords: &[IVec2], bounds: Bounds, constraint: usize) -> usize {
+    bounds
+        .positions()
+        .filter(|coord| {
+            // Check if the total Manhattan distance to all points is less than 10000
+            coords.iter().map(|c| manhattan(c, coord)).sum::<usize>() < constraint
         })
         .count()
 }
 
 pub fn part_one(input: &str) -> Option<usize> {
     let coordinates = parse(input);
-    let (upper_left, lower_right) = find_bounds(&coordinates);
+    let bounds = Bounds::of(coordinates.iter().copied())?;
 
     coordinates
         .iter()
-        .filter(|&c| {
-            !(c.x == upper_left.x
-                || c.x == lower_right.x
-                || c.y == upper_left.y
-                || c.y == lower_right.y)
-        })
-        .filter_map(|coord| {
-            find_area(coord, &coordinates, &upper_left, &lower_right).map(|a| a.len())
-        })
+        .filter(|&&c| !bounds.on_edge(c))
+        .filter_map(|coord| find_area(coord, &coordinates, &bounds).map(|a| a.len()))
         .max()
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
     let coords = parse(input);
-    let (upper_left, lower_right) = find_bounds(&coords);
-
-    Some(find_safe_region_size(
-        &coords,
-        &upper_left,
-        &lower_right,
-        10000,
-    ))
+    let bounds = Bounds::of(coords.iter().copied())?;
+
+    Some(find_safe_region_size(&coords, bounds, 10000))
 }
 
 #[cfg(test)]
@@ -154,8 +103,8 @@ mod tests {
     #[test]
     fn test_find_safe_region_size() {
         let coords = parse(&advent_of_code::template::read_file("examples", DAY));
-        let (upper_left, lower_right) = find_bounds(&coords);
-        let result = find_safe_region_size(&coords, &upper_left, &lower_right, 32);
+        let bounds = Bounds::of(coords.iter().copied()).unwrap();
+        let result = find_safe_region_size(&coords, bounds, 32);
         assert_eq!(result, 16);
     }
 }
@@ -1,6 +1,9 @@
+use advent_of_code::grid::Bounds;
+use advent_of_code::render::{Colour, FrameSink, Glyph, Renderer};
+use advent_of_code::Grid;
 use glam::IVec2;
 
-advent_of_code::solution!(10);
+advent_of_code::solution!(10, visualize 1);
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 struct LightPoint {
@@ -31,33 +34,43 @@ fn parse_light_point(line: &str) -> Option<LightPoint> {
     ))
 }
 
-fn find_bounds(points: &[LightPoint]) -> (IVec2, IVec2) {
-    let min_x = points.iter().map(|p| p.position.x).min().unwrap();
-    let min_y = points.iter().map(|p| p.position.y).min().unwrap();
+fn find_bounds(points: &[LightPoint]) -> Option<Bounds> {
+    Bounds::of(points.iter().map(|p| p.position))
+}
 
-    let max_x = points.iter().map(|p| p.position.x).max().unwrap();
-    let max_y = points.iter().map(|p| p.position.y).max().unwrap();
+/// Frames are only drawn once the points are this close together.
+const VISIBLE_HEIGHT: i32 = 40;
 
-    (IVec2::new(min_x, min_y), IVec2::new(max_x, max_y))
-}
+/// The positions lit by at least one point.
+struct Sky(Grid<()>);
 
-fn format_points(points: &[LightPoint]) -> String {
-    let mut result = "".to_owned();
-    let (upper_left, lower_right) = find_bounds(points);
-
-    for y in upper_left.y..=lower_right.y {
-        for x in upper_left.x..=lower_right.x {
-            if points.iter().any(|p| p.position == IVec2::new(x, y)) {
-                result.push('#');
-            } else {
-                result.push('.');
-            }
+impl Sky {
+    fn new(points: &[LightPoint]) -> Self {
+        let mut lights = Grid::sparse();
+        for point in points {
+            lights.insert(point.position, ());
         }
 
-        result.push('\n');
+        Self(lights)
     }
+}
 
-    result
+impl Renderer for Sky {
+    fn bounds(&self) -> Option<Bounds> {
+        self.0.bounds()
+    }
+
+    fn glyph(&self, pos: IVec2) -> Glyph {
+        if self.0.contains(pos) {
+            Glyph::coloured('#', Colour::Yellow)
+        } else {
+            Glyph::plain('.')
+        }
+    }
+}
+
+fn format_points(points: &[LightPoint]) -> String {
+    Sky::new(points).render(None, false)
 }
 
 fn tick(points: &mut [LightPoint]) {
@@ -66,23 +79,27 @@ fn tick(points: &mut [LightPoint]) {
     }
 }
 
-pub fn part_one(input: &str) -> Option<String> {
+pub fn part_one(input: &str, mut sink: Option<&mut FrameSink>) -> Option<String> {
     let mut points = input
         .lines()
         .filter_map(parse_light_point)
         .collect::<Vec<_>>();
 
-    let (mut upper_left, mut lower_right) = find_bounds(&points);
+    let mut bounds = find_bounds(&points)?;
     let mut prev_state = points.clone();
     let mut prev_height = i32::MAX;
 
-    while lower_right.y - upper_left.y < prev_height {
-        prev_height = lower_right.y - upper_left.y;
+    while bounds.height() < prev_height {
+        prev_height = bounds.height();
         prev_state = points.clone();
 
+        if let Some(sink) = sink.as_mut().filter(|_| bounds.height() < VISIBLE_HEIGHT) {
+            sink.emit(&Sky::new(&points));
+        }
+
         tick(&mut points);
 
-        (upper_left, lower_right) = find_bounds(&points);
+        bounds = find_bounds(&points)?;
     }
 
     Some(format_points(&prev_state))
@@ -94,17 +111,17 @@ pub fn part_two(input: &str) -> Option<u32> {
         .filter_map(parse_light_point)
         .collect::<Vec<_>>();
 
-    let (mut upper_left, mut lower_right) = find_bounds(&points);
+    let mut bounds = find_bounds(&points)?;
     let mut prev_height = i32::MAX;
 
     let mut seconds = 0;
 
-    while lower_right.y - upper_left.y < prev_height {
-        prev_height = lower_right.y - upper_left.y;
+    while bounds.height() < prev_height {
+        prev_height = bounds.height();
 
         tick(&mut points);
 
-        (upper_left, lower_right) = find_bounds(&points);
+        bounds = find_bounds(&points)?;
 
         seconds += 1;
     }
@@ -118,7 +135,7 @@ mod tests {
 
     #[test]
     fn test_part_one() {
-        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
+        let result = part_one(&advent_of_code::template::read_file("examples", DAY), None);
         assert_eq!(result, Some(String::from("#...#..###\n#...#...#.\n#...#...#.\n#####...#.\n#...#...#.\n#...#...#.\n#...#...#.\n#...#..###\n")));
     }
 
@@ -1,17 +1,20 @@
+use advent_of_code::grid::{Bounds, Direction, ReadingOrder};
+use advent_of_code::render::{Colour, FrameSink, Glyph, Renderer};
+use advent_of_code::Grid;
 use glam::IVec2;
 
-advent_of_code::solution!(13);
+advent_of_code::solution!(13, visualize 1 2);
 
 #[derive(Debug, Clone, Copy)]
 struct Cart {
     position: IVec2,
-    direction: IVec2,
+    direction: Direction,
     next_turn_decision: u8,
     is_crashed: bool,
 }
 
 impl Cart {
-    fn new(position: IVec2, direction: IVec2) -> Self {
+    fn new(position: IVec2, direction: Direction) -> Self {
         Self {
             position,
             direction,
@@ -21,122 +24,49 @@ impl Cart {
     }
 }
 
-struct TrackPiece {
-    position: IVec2,
-    neighbors: Vec<IVec2>,
-    piece_type: char,
+/// The track piece at each position. Carts are replaced by the straight piece under them.
+type Track = Grid<char>;
+
+/// The track with the carts on it, for drawing.
+struct Mine<'a> {
+    track: &'a Track,
+    carts: &'a [Cart],
 }
 
-impl TrackPiece {
-    fn new(x: i32, y: i32, piece_type: char) -> Self {
-        Self {
-            position: IVec2::new(x, y),
-            neighbors: Vec::new(),
-            piece_type,
-        }
+impl Renderer for Mine<'_> {
+    fn bounds(&self) -> Option<Bounds> {
+        self.track.bounds()
     }
-}
 
-struct Track {
-    pieces: Vec<TrackPiece>,
-}
+    fn glyph(&self, pos: IVec2) -> Glyph {
+        let mut carts = self.carts.iter().filter(|c| c.position == pos);
 
-impl Track {
-    fn new() -> Self {
-        Self { pieces: Vec::new() }
+        match (carts.next(), carts.next()) {
+            (Some(_), Some(_)) => Glyph::coloured('X', Colour::Red),
+            (Some(cart), None) if cart.is_crashed => Glyph::coloured('X', Colour::Red),
+            (Some(cart), None) => Glyph::coloured(cart.direction.arrow(), Colour::Yellow),
+            _ => Glyph::plain(self.track.get(pos).copied().unwrap_or(' ')),
+        }
     }
 }
 
 fn parse(input: &str) -> Option<(Track, Vec<Cart>)> {
-    let mut carts = Vec::new();
-    let mut track = Track::new();
-
-    let dirs = [
-        IVec2::new(0, -1),
-        IVec2::new(0, 1),
-        IVec2::new(-1, 0),
-        IVec2::new(1, 0),
-    ];
-
-    for (y, line) in input.lines().enumerate() {
-        let mut prev = ' ';
-
-        for (x, c) in line.chars().enumerate() {
-            if c == ' ' {
-                prev = c;
-                continue;
-            }
-
-            let mut piece = TrackPiece::new(x as i32, y as i32, c);
-
-            match c {
-                '/' => {
-                    if prev == '-' {
-                        piece.neighbors.push(piece.position + dirs[2]);
-                        piece.neighbors.push(piece.position + dirs[0]);
-                    } else {
-                        piece.neighbors.push(piece.position + dirs[3]);
-                        piece.neighbors.push(piece.position + dirs[1]);
-                    }
-                }
-                '\\' => {
-                    if prev == '-' {
-                        piece.neighbors.push(piece.position + dirs[2]);
-                        piece.neighbors.push(piece.position + dirs[1]);
-                    } else {
-                        piece.neighbors.push(piece.position + dirs[3]);
-                        piece.neighbors.push(piece.position + dirs[0]);
-                    }
-                }
-                '-' => {
-                    piece.neighbors.push(piece.position + dirs[2]);
-                    piece.neighbors.push(piece.position + dirs[3]);
-                }
-                '|' => {
-                    piece.neighbors.push(piece.position + dirs[0]);
-                    piece.neighbors.push(piece.position + dirs[1]);
-                }
-                '+' => {
-                    piece.neighbors.push(piece.position + dirs[0]);
-                    piece.neighbors.push(piece.position + dirs[1]);
-                    piece.neighbors.push(piece.position + dirs[2]);
-                    piece.neighbors.push(piece.position + dirs[3]);
-                }
-                '>' => {
-                    piece.neighbors.push(piece.position + dirs[2]);
-                    piece.neighbors.push(piece.position + dirs[3]);
-                    piece.piece_type = '-';
-
-                    carts.push(Cart::new(piece.position, dirs[3]));
-                }
-                '<' => {
-                    piece.neighbors.push(piece.position + dirs[2]);
-                    piece.neighbors.push(piece.position + dirs[3]);
-                    piece.piece_type = '-';
-
-                    carts.push(Cart::new(piece.position, dirs[2]));
-                }
-                '^' => {
-                    piece.neighbors.push(piece.position + dirs[0]);
-                    piece.neighbors.push(piece.position + dirs[1]);
-                    piece.piece_type = '|';
-
-                    carts.push(Cart::new(piece.position, dirs[0]));
-                }
-                'v' => {
-                    piece.neighbors.push(piece.position + dirs[0]);
-                    piece.neighbors.push(piece.position + dirs[1]);
-                    piece.piece_type = '|';
-
-                    carts.push(Cart::new(piece.position, dirs[1]));
-                }
-                _ => {}
-            }
-
-            track.pieces.push(piece);
-
-            prev = c;
-        }
+    let mut track =
+        Grid::parse_sparse(input, ' ', |c| "/\\-|+<>^v".contains(c).then_some(c)).ok()?;
+
+    let carts = track
+        .iter()
+        .filter_map(|(position, &c)| {
+            Direction::from_arrow(c).map(|direction| Cart::new(position, direction))
+        })
+        .collect::<Vec<_>>();
+
+    for cart in &carts {
+        track[cart.position] = if cart.direction.is_vertical() {
+            '|'
+        } else {
+            '-'
+        };
     }
 
     Some((track, carts))
@@ -158,119 +88,43 @@ fn detect_crash(carts: &[Cart]) -> Option<IVec2> {
     None
 }
 
-fn turn_left(direction: IVec2) -> Option<IVec2> {
-    let dirs = [
-        IVec2::new(0, -1),
-        IVec2::new(0, 1),
-        IVec2::new(-1, 0),
-        IVec2::new(1, 0),
-    ];
-
-    if direction == dirs[0] {
-        Some(dirs[2])
-    } else if direction == dirs[1] {
-        Some(dirs[3])
-    } else if direction == dirs[2] {
-        Some(dirs[1])
-    } else if direction == dirs[3] {
-        Some(dirs[0])
-    } else {
-        panic!("invalid move");
-    }
-}
-
-fn turn_right(direction: IVec2) -> Option<IVec2> {
-    let dirs = [
-        IVec2::new(0, -1),
-        IVec2::new(0, 1),
-        IVec2::new(-1, 0),
-        IVec2::new(1, 0),
-    ];
-
-    if direction == dirs[0] {
-        Some(dirs[3])
-    } else if direction == dirs[1] {
-        Some(dirs[2])
-    } else if direction == dirs[2] {
-        Some(dirs[0])
-    } else if direction == dirs[3] {
-        Some(dirs[1])
-    } else {
-        panic!("invalid move");
-    }
-}
+fn update_direction(track: &Track, cart: &Cart) -> Option<Direction> {
+    let direction = cart.direction;
 
-fn update_direction(track: &Track, cart: &Cart) -> Option<IVec2> {
-    let track_piece = track.pieces.iter().find(|p| p.position == cart.position)?;
-
-    let dirs = [
-        IVec2::new(0, -1),
-        IVec2::new(0, 1),
-        IVec2::new(-1, 0),
-        IVec2::new(1, 0),
-    ];
-
-    if track_piece.piece_type == '-'
-        || track_piece.piece_type == '|'
-        || track_piece.piece_type == '+'
-    {
-        Some(cart.direction)
-    } else if track_piece.piece_type == '/' {
-        if cart.direction == dirs[0] {
-            Some(dirs[3])
-        } else if cart.direction == dirs[1] {
-            Some(dirs[2])
-        } else if cart.direction == dirs[2] {
-            Some(dirs[1])
-        } else if cart.direction == dirs[3] {
-            Some(dirs[0])
-        } else {
-            panic!("invalid move");
-            //None
-        }
-    } else if track_piece.piece_type == '\\' {
-        if cart.direction == dirs[0] {
-            Some(dirs[2])
-        } else if cart.direction == dirs[1] {
-            Some(dirs[3])
-        } else if cart.direction == dirs[2] {
-            Some(dirs[0])
-        } else if cart.direction == dirs[3] {
-            Some(dirs[1])
-        } else {
-            panic!("invalid move");
-            //None
-        }
-    } else {
-        panic!("invalid move");
-        //None
+    match *track.get(cart.position)? {
+        '-' | '|' | '+' => Some(direction),
+        '/' if direction.is_vertical() => Some(direction.turn_right()),
+        '/' => Some(direction.turn_left()),
+        '\\' if direction.is_vertical() => Some(direction.turn_left()),
+        '\\' => Some(direction.turn_right()),
+        _ => panic!("invalid move"),
     }
 }
 
 fn move_cart(track: &Track, cart: &Cart) -> Option<Cart> {
-    let track_piece = track.pieces.iter().find(|p| p.position == cart.position)?;
+    let track_piece = *track.get(cart.position)?;
 
     let mut next_cart = *cart;
 
-    if track_piece.neighbors.len() == 4 {
+    if track_piece == '+' {
         // its an intersection
         match next_cart.next_turn_decision {
             0 => {
-                next_cart.direction = turn_left(next_cart.direction)?;
-                next_cart.position += next_cart.direction;
+                next_cart.direction = next_cart.direction.turn_left();
+                next_cart.position = next_cart.direction.step(next_cart.position);
                 next_cart.direction = update_direction(track, &next_cart)?;
 
                 next_cart.next_turn_decision = 1;
             }
             1 => {
-                next_cart.position += next_cart.direction;
+                next_cart.position = next_cart.direction.step(next_cart.position);
                 next_cart.direction = update_direction(track, &next_cart)?;
 
                 next_cart.next_turn_decision = 2;
             }
             2 => {
-                next_cart.direction = turn_right(next_cart.direction)?;
-                next_cart.position += next_cart.direction;
+                next_cart.direction = next_cart.direction.turn_right();
+                next_cart.position = next_cart.direction.step(next_cart.position);
                 next_cart.direction = update_direction(track, &next_cart)?;
 
                 next_cart.next_turn_decision = 0;
@@ -279,7 +133,7 @@ fn move_cart(track: &Track, cart: &Cart) -> Option<Cart> {
         }
     } else {
         // move forward
-        next_cart.position += next_cart.direction;
+        next_cart.position = next_cart.direction.step(next_cart.position);
         next_cart.direction = update_direction(track, &next_cart)?;
     }
 
@@ -291,12 +145,7 @@ fn move_cart(track: &Track, cart: &Cart) -> Option<Cart> {
 }
 
 fn tick(track: &Track, carts: &mut [Cart]) -> Option<IVec2> {
-    carts.sort_by(|a, b| {
-        a.position
-            .y
-            .cmp(&b.position.y)
-            .then(a.position.x.cmp(&b.position.x))
-    });
+    carts.sort_by_key(|cart| ReadingOrder(cart.position));
 
     for i in 0..carts.len() {
         carts[i] = move_cart(track, &carts[i])?;
@@ -312,12 +161,7 @@ fn tick(track: &Track, carts: &mut [Cart]) -> Option<IVec2> {
 }
 
 fn tick2(track: &Track, carts: &mut [Cart]) {
-    carts.sort_by(|a, b| {
-        a.position
-            .y
-            .cmp(&b.position.y)
-            .then(a.position.x.cmp(&b.position.x))
-    });
+    carts.sort_by_key(|cart| ReadingOrder(cart.position));
 
     for i in 0..carts.len() {
         carts[i] = move_cart(track, &carts[i]).unwrap();
@@ -334,24 +178,38 @@ fn tick2(track: &Track, carts: &mut [Cart]) {
     }
 }
 
-pub fn part_one(input: &str) -> Option<String> {
+pub fn part_one(input: &str, mut sink: Option<&mut FrameSink>) -> Option<String> {
     let (track, mut carts) = parse(input)?;
 
     loop {
         let result = tick(&track, &mut carts);
 
+        if let Some(sink) = sink.as_mut() {
+            sink.emit(&Mine {
+                track: &track,
+                carts: &carts,
+            });
+        }
+
         if let Some(crash) = result {
             return Some(format!("{},{}", crash.x, crash.y));
         }
     }
 }
 
-pub fn part_two(input: &str) -> Option<String> {
+pub fn part_two(input: &str, mut sink: Option<&mut FrameSink>) -> Option<String> {
     let (track, mut carts) = parse(input)?;
 
     loop {
         tick2(&track, &mut carts);
 
+        if let Some(sink) = sink.as_mut() {
+            sink.emit(&Mine {
+                track: &track,
+                carts: &carts,
+            });
+        }
+
         carts.retain(|c| !c.is_crashed);
 
         if carts.len() == 1 {
@@ -366,15 +224,16 @@ mod tests {
 
     #[test]
     fn test_part_one() {
-        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
+        let result = part_one(&advent_of_code::template::read_file("examples", DAY), None);
         assert_eq!(result, Some("7,3".to_owned()));
     }
 
     #[test]
     fn test_part_two() {
-        let result = part_two(&advent_of_code::template::read_file_part(
-            "examples", DAY, 2,
-        ));
+        let result = part_two(
+            &advent_of_code::template::read_file_part("examples", DAY, 2),
+            None,
+        );
         assert_eq!(result, Some("6,4".to_owned()));
     }
 }
@@ -1,8 +1,11 @@
+use advent_of_code::grid::{Bounds, Direction, ReadingOrder};
+use advent_of_code::render::{Colour, FrameSink, Glyph, Renderer};
+use advent_of_code::Grid;
 use glam::IVec2;
 use itertools::Itertools;
 use pathfinding::prelude::bfs;
 
-advent_of_code::solution!(15);
+advent_of_code::solution!(15, visualize 1);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum BattlerType {
@@ -40,97 +43,50 @@ impl Battler {
 
 #[derive(Debug, Clone)]
 struct Battle {
-    terrain: Vec<IVec2>,
+    /// Whether each position is a wall.
+    terrain: Grid<bool>,
     battlers: Vec<Battler>,
     rounds: usize,
     battle_over: bool,
 }
 
 impl Battle {
-    fn new() -> Self {
+    fn new(terrain: Grid<bool>) -> Self {
         Self {
-            terrain: Vec::new(),
+            terrain,
             battlers: Vec::new(),
             rounds: 0,
             battle_over: false,
         }
     }
 
-    #[allow(dead_code)]
-    fn width(&self) -> i32 {
-        self.terrain.iter().map(|vec| vec.x).max().unwrap() + 1
-    }
-
-    #[allow(dead_code)]
-    fn height(&self) -> i32 {
-        self.terrain.iter().map(|vec| vec.y).max().unwrap() + 1
+    fn is_open(&self, pos: IVec2) -> bool {
+        self.terrain.get(pos) == Some(&false)
+            && !self
+                .battlers
+                .iter()
+                .any(|b| b.position == pos && !b.is_dead)
     }
 
     fn successors(&self, pos: &IVec2) -> Vec<IVec2> {
-        let dirs = [
-            IVec2::new(0, -1) + *pos,
-            IVec2::new(-1, 0) + *pos,
-            IVec2::new(1, 0) + *pos,
-            IVec2::new(0, 1) + *pos,
-        ];
-
-        dirs.iter()
-            .filter(|&d| {
-                !self.terrain.contains(d)
-                    && !self.battlers.iter().any(|b| b.position == *d && !b.is_dead)
-            })
-            .cloned()
+        self.terrain
+            .neighbors4(*pos)
+            .filter(|&n| self.is_open(n))
             .collect()
     }
 
     fn choose_target(&self, battler: usize) -> Option<Vec<IVec2>> {
-        let dirs = [
-            IVec2::new(0, -1),
-            IVec2::new(-1, 0),
-            IVec2::new(1, 0),
-            IVec2::new(0, 1),
-        ];
-
         let battler_obj = self.battlers[battler];
 
         let open_adjacents = self
             .battlers
             .iter()
             .filter(|b| b.battler_type != battler_obj.battler_type && !b.is_dead)
-            .map(|b| {
-                dirs.iter().filter_map(|d| {
-                    let adjacent = *d + b.position;
-                    if !self.terrain.contains(&adjacent)
-                        && !self
-                            .battlers
-                            .iter()
-                            .any(|b| b.position == adjacent && !b.is_dead)
-                    {
-                        Some(adjacent)
-                    } else {
-                        None
-                    }
-                })
-            });
+            .map(|b| self.successors(&b.position));
 
         let mut shortest_paths = Vec::new();
 
-        let battler_open_adjacents = dirs
-            .iter()
-            .filter_map(|d| {
-                let adjacent = *d + battler_obj.position;
-                if !self.terrain.contains(&adjacent)
-                    && !self
-                        .battlers
-                        .iter()
-                        .any(|b| b.position == adjacent && !b.is_dead)
-                {
-                    Some(adjacent)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
+        let battler_open_adjacents = self.successors(&battler_obj.position);
 
         for other in open_adjacents.flatten().unique() {
             for &battler_open_adjacent in battler_open_adjacents.iter() {
@@ -146,42 +102,24 @@ impl Battle {
             }
         }
 
-        shortest_paths.sort_by(|a, b| {
-            let a_last = a.last().unwrap();
-            let b_last = b.last().unwrap();
-            a.len()
-                .cmp(&b.len())
-                .then(a_last.y.cmp(&b_last.y).then(a_last.x.cmp(&b_last.x)))
-                .then(a[0].y.cmp(&b[0].y).then(a[0].x.cmp(&b[0].x)))
-        });
-
-        if !shortest_paths.is_empty() {
-            Some(shortest_paths[0].clone())
-        } else {
-            None
-        }
+        shortest_paths.into_iter().min_by_key(|path| {
+            (
+                path.len(),
+                ReadingOrder(path[path.len() - 1]),
+                ReadingOrder(path[0]),
+            )
+        })
     }
 
     fn attackable(&mut self, battler: &Battler) -> Option<&mut Battler> {
-        let dirs = [
-            IVec2::new(0, -1) + battler.position,
-            IVec2::new(-1, 0) + battler.position,
-            IVec2::new(1, 0) + battler.position,
-            IVec2::new(0, 1) + battler.position,
-        ];
-
         self.battlers
             .iter_mut()
             .filter(|b| {
-                !b.is_dead && b.battler_type != battler.battler_type && dirs.contains(&b.position)
-            })
-            .sorted_by(|a, b| {
-                a.health
-                    .cmp(&b.health)
-                    .then(a.position.y.cmp(&b.position.y))
-                    .then(a.position.x.cmp(&b.position.x))
+                !b.is_dead
+                    && b.battler_type != battler.battler_type
+                    && Direction::neighbors(battler.position).any(|n| n == b.position)
             })
-            .next()
+            .min_by_key(|b| (b.health, ReadingOrder(b.position)))
     }
 
     fn take_turn(&mut self, battler: usize) {
@@ -226,12 +164,7 @@ impl Battle {
     fn round(&mut self) {
         self.rounds += 1;
 
-        self.battlers.sort_by(|&a, &b| {
-            a.position
-                .y
-                .cmp(&b.position.y)
-                .then(a.position.x.cmp(&b.position.x))
-        });
+        self.battlers.sort_by_key(|b| ReadingOrder(b.position));
 
         for i in 0..self.battlers.len() {
             if self.victory_check() {
@@ -241,50 +174,48 @@ impl Battle {
             self.take_turn(i);
         }
     }
+}
 
-    #[allow(dead_code)]
-    fn draw(&self) {
-        let width = self.width();
-        let height = self.height();
-
-        if self.rounds == 0 {
-            println!("Initially:");
-        } else if self.rounds == 1 {
-            println!("After 1 round:");
-        } else {
-            println!("After {} rounds:", self.rounds);
-        }
+impl Renderer for Battle {
+    fn bounds(&self) -> Option<Bounds> {
+        self.terrain.bounds()
+    }
 
-        for y in 0..height {
-            let mut row_battlers = Vec::new();
-
-            for x in 0..width {
-                let tile = IVec2::new(x, y);
-                let battler = self
-                    .battlers
-                    .iter()
-                    .find(|g| g.position == tile && !g.is_dead);
-
-                if self.terrain.contains(&tile) {
-                    print!("#");
-                } else if let Some(battler) = battler {
-                    let battler_type = if battler.battler_type == BattlerType::Goblin {
-                        "G"
-                    } else {
-                        "E"
-                    };
-
-                    print!("{battler_type}");
-                    row_battlers.push(format!("{}({})", battler_type, battler.health));
-                } else {
-                    print!(".");
-                }
-            }
+    fn glyph(&self, pos: IVec2) -> Glyph {
+        let battler = self
+            .battlers
+            .iter()
+            .find(|b| b.position == pos && !b.is_dead);
 
-            println!("   {}", row_battlers.iter().join(", "));
+        match battler {
+            _ if self.terrain.get(pos) != Some(&false) => Glyph::coloured('#', Colour::Grey),
+            Some(b) if b.battler_type == BattlerType::Goblin => Glyph::coloured('G', Colour::Red),
+            Some(_) => Glyph::coloured('E', Colour::Green),
+            None => Glyph::plain('.'),
         }
+    }
+
+    fn caption(&self) -> Option<String> {
+        Some(match self.rounds {
+            0 => "Initially:".to_owned(),
+            1 => "After 1 round:".to_owned(),
+            rounds => format!("After {rounds} rounds:"),
+        })
+    }
 
-        println!();
+    fn annotate_row(&self, y: i32) -> Option<String> {
+        let row = self
+            .battlers
+            .iter()
+            .filter(|b| b.position.y == y && !b.is_dead)
+            .sorted_by_key(|b| b.position.x)
+            .map(|b| match b.battler_type {
+                BattlerType::Goblin => format!("G({})", b.health),
+                BattlerType::Elf => format!("E({})", b.health),
+            })
+            .join(", ");
+
+        (!row.is_empty()).then_some(row)
     }
 }
 
@@ -294,43 +225,40 @@ fn parse_battle(
     goblin_starting_attack: i32,
     elf_starting_attack: i32,
 ) -> Option<Battle> {
-    let mut battle = Battle::new();
+    let map = Grid::parse_dense(input, |c| "#.GE".contains(c).then_some(c)).ok()?;
+    let mut battle = Battle::new(map.map(|_, &c| c == '#'));
 
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            match c {
-                '#' => {
-                    battle.terrain.push(IVec2::new(x as i32, y as i32));
-                }
-                'G' => battle.battlers.push(Battler::new(
-                    BattlerType::Goblin,
-                    IVec2::new(x as i32, y as i32),
-                    starting_health,
-                    goblin_starting_attack,
-                )),
-                'E' => battle.battlers.push(Battler::new(
-                    BattlerType::Elf,
-                    IVec2::new(x as i32, y as i32),
-                    starting_health,
-                    elf_starting_attack,
-                )),
-                '.' => {}
-                _ => panic!("Invalid tile"),
-            }
-        }
+    for (position, &c) in map.iter() {
+        let (battler_type, attack) = match c {
+            'G' => (BattlerType::Goblin, goblin_starting_attack),
+            'E' => (BattlerType::Elf, elf_starting_attack),
+            _ => continue,
+        };
+
+        battle.battlers.push(Battler::new(
+            battler_type,
+            position,
+            starting_health,
+            attack,
+        ));
     }
 
     Some(battle)
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
+pub fn part_one(input: &str, mut sink: Option<&mut FrameSink>) -> Option<usize> {
     let mut battle = parse_battle(input, 200, 3, 3)?;
 
-    //battle.draw();
+    if let Some(sink) = sink.as_mut() {
+        sink.emit(&battle);
+    }
 
     while !battle.battle_over {
         battle.round();
-        //battle.draw();
+
+        if let Some(sink) = sink.as_mut() {
+            sink.emit(&battle);
+        }
     }
 
     let winning_team_health: i32 = battle
@@ -352,11 +280,8 @@ pub fn part_two(input: &str) -> Option<usize> {
             .filter(|b| b.battler_type == BattlerType::Elf && !b.is_dead)
             .count();
 
-        //battle.draw();
-
         while !battle.battle_over {
             battle.round();
-            //battle.draw();
         }
 
         let elves_count_after = battle
@@ -386,39 +311,43 @@ mod tests {
 
     #[test]
     fn test_part_one() {
-        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
+        let result = part_one(&advent_of_code::template::read_file("examples", DAY), None);
         assert_eq!(result, Some(27730));
     }
 
     #[test]
     fn test_part_one_2() {
-        let result = part_one(&advent_of_code::template::read_file_part(
-            "examples", DAY, 2,
-        ));
+        let result = part_one(
+            &advent_of_code::template::read_file_part("examples", DAY, 2),
+            None,
+        );
         assert_eq!(result, Some(36334));
     }
 
     #[test]
     fn test_part_one_3() {
-        let result = part_one(&advent_of_code::template::read_file_part(
-            "examples", DAY, 3,
-        ));
+        let result = part_one(
+            &advent_of_code::template::read_file_part("examples", DAY, 3),
+            None,
+        );
         assert_eq!(result, Some(27755));
     }
 
     #[test]
     fn test_part_one_4() {
-        let result = part_one(&advent_of_code::template::read_file_part(
-            "examples", DAY, 4,
-        ));
+        let result = part_one(
+            &advent_of_code::template::read_file_part("examples", DAY, 4),
+            None,
+        );
         assert_eq!(result, Some(28944));
     }
 
     #[test]
     fn test_part_one_5() {
-        let result = part_one(&advent_of_code::template::read_file_part(
-            "examples", DAY, 5,
-        ));
+        let result = part_one(
+            &advent_of_code::template::read_file_part("examples", DAY, 5),
+            None,
+        );
         assert_eq!(result, Some(18740));
     }
 
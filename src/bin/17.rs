@@ -1,126 +1,147 @@
-use std::collections::HashSet;
-
+use advent_of_code::grid::Bounds;
+use advent_of_code::render::{Colour, FrameSink, Glyph, Renderer};
+use advent_of_code::Grid;
 use glam::IVec2;
 
-advent_of_code::solution!(17);
+advent_of_code::solution!(17, visualize 1);
 
 fn in_range(x: i32, a0: i32, a1: i32) -> bool {
     x >= a0 && x <= a1
 }
 
-struct Bounds {
-    upper_left: IVec2,
-    lower_right: IVec2,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ground {
+    Clay,
+    /// Water that has passed through.
+    Flowing,
+    /// Water that has come to rest.
+    Settled,
 }
 
-impl Bounds {
-    fn new(upper_left: IVec2, lower_right: IVec2) -> Self {
-        Self {
-            upper_left,
-            lower_right,
-        }
-    }
-}
-
-struct GroundSlice {
-    clay: HashSet<IVec2>,
-    settled: HashSet<IVec2>,
-    flowing: HashSet<IVec2>,
+struct GroundSlice<'a> {
+    ground: Grid<Ground>,
     bounds: Bounds,
     max_y: i32,
     min_y: i32,
+    sink: Option<&'a mut FrameSink>,
 }
 
-impl GroundSlice {
-    fn new(clay: HashSet<IVec2>) -> Self {
-        let x_min = clay.iter().map(|p| p.x).min().unwrap();
-        let x_max = clay.iter().map(|p| p.x).max().unwrap();
-        let y_max = clay.iter().map(|p| p.y).max().unwrap();
-
-        let max_y = clay.iter().map(|p| p.y).max().unwrap();
-        let min_y = clay.iter().map(|p| p.y).min().unwrap();
-
-        Self {
-            clay,
-            settled: HashSet::new(),
-            flowing: HashSet::new(),
-            bounds: Bounds::new(IVec2::new(x_min - 1, 0), IVec2::new(x_max + 1, y_max)),
-            max_y,
-            min_y,
+impl GroundSlice<'_> {
+    fn new(clay: impl IntoIterator<Item = IVec2>) -> Option<Self> {
+        let mut ground = Grid::sparse();
+        for pos in clay {
+            ground.insert(pos, Ground::Clay);
         }
+
+        let clay_bounds = ground.bounds()?;
+
+        Some(Self {
+            ground,
+            bounds: Bounds::new(
+                IVec2::new(clay_bounds.min.x - 1, 0),
+                IVec2::new(clay_bounds.max.x + 1, clay_bounds.max.y),
+            ),
+            max_y: clay_bounds.max.y,
+            min_y: clay_bounds.min.y,
+            sink: None,
+        })
     }
 
-    #[allow(dead_code)]
-    fn draw(&self) {
-        for y in self.bounds.upper_left.y..=self.bounds.lower_right.y {
-            for x in self.bounds.upper_left.x..=self.bounds.lower_right.x {
-                let square = IVec2::new(x, y);
-
-                if self.clay.contains(&square) {
-                    print!("#");
-                } else if square == IVec2::new(500, 0) {
-                    print!("+");
-                } else if self.settled.contains(&square) {
-                    print!("~");
-                } else if self.flowing.contains(&square) {
-                    print!("|");
-                } else {
-                    print!(".");
-                }
-            }
+    fn is_clay(&self, pos: &IVec2) -> bool {
+        self.ground.get(*pos) == Some(&Ground::Clay)
+    }
 
-            println!();
-        }
+    /// Whether water has reached `pos`, settled or not.
+    fn is_wet(&self, pos: &IVec2) -> bool {
+        matches!(
+            self.ground.get(*pos),
+            Some(Ground::Flowing | Ground::Settled)
+        )
+    }
 
-        println!();
+    fn is_settled(&self, pos: &IVec2) -> bool {
+        self.ground.get(*pos) == Some(&Ground::Settled)
+    }
+
+    /// Water counted towards the answers.
+    fn count(&self, matches: impl Fn(Ground) -> bool) -> usize {
+        self.ground
+            .iter()
+            .filter(|&(pos, &cell)| matches(cell) && in_range(pos.y, self.min_y, self.max_y))
+            .count()
+    }
+
+    /// Sends the current state to the frame sink, if visualizing.
+    fn show(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            sink.emit(self);
+            self.sink = Some(sink);
+        }
     }
 
     fn fill(&mut self, start: IVec2, dir: IVec2) -> bool {
-        self.flowing.insert(start);
+        if self.ground.get(start).is_none() {
+            self.ground.insert(start, Ground::Flowing);
+        }
 
         let below = IVec2::new(start.x, start.y + 1);
 
-        if !self.clay.contains(&below)
-            && !self.flowing.contains(&below)
-            && in_range(below.y, 1, self.bounds.lower_right.y)
-        {
+        if !self.is_clay(&below) && !self.is_wet(&below) && in_range(below.y, 1, self.max_y) {
             self.fill(below, IVec2::new(0, 1));
         }
 
-        if !self.clay.contains(&below) && !self.settled.contains(&below) {
+        if !self.is_clay(&below) && !self.is_settled(&below) {
             return false;
         }
 
         let mut left = IVec2::new(start.x - 1, start.y);
         let mut right = IVec2::new(start.x + 1, start.y);
 
-        let left_filled = self.clay.contains(&left)
-            || !self.flowing.contains(&left) && self.fill(left, IVec2::new(-1, 0));
-        let right_filled = self.clay.contains(&right)
-            || !self.flowing.contains(&right) && self.fill(right, IVec2::new(1, 0));
+        let left_filled =
+            self.is_clay(&left) || !self.is_wet(&left) && self.fill(left, IVec2::new(-1, 0));
+        let right_filled =
+            self.is_clay(&right) || !self.is_wet(&right) && self.fill(right, IVec2::new(1, 0));
 
         if dir == IVec2::new(0, 1) && left_filled && right_filled {
-            self.settled.insert(start);
+            self.ground.insert(start, Ground::Settled);
 
-            while self.flowing.contains(&left) {
-                self.settled.insert(left);
+            while self.is_wet(&left) {
+                self.ground.insert(left, Ground::Settled);
                 left.x -= 1;
             }
 
-            while self.flowing.contains(&right) {
-                self.settled.insert(right);
+            while self.is_wet(&right) {
+                self.ground.insert(right, Ground::Settled);
                 right.x += 1;
             }
+
+            self.show();
         }
 
-        dir == IVec2::new(-1, 0) && (left_filled || self.clay.contains(&left))
-            || dir == IVec2::new(1, 0) && (right_filled || self.clay.contains(&right))
+        dir == IVec2::new(-1, 0) && (left_filled || self.is_clay(&left))
+            || dir == IVec2::new(1, 0) && (right_filled || self.is_clay(&right))
+    }
+}
+
+impl Renderer for GroundSlice<'_> {
+    fn bounds(&self) -> Option<Bounds> {
+        Some(self.bounds)
+    }
+
+    fn glyph(&self, pos: IVec2) -> Glyph {
+        match self.ground.get(pos) {
+            Some(Ground::Clay) => Glyph::coloured('#', Colour::Yellow),
+            _ if pos == IVec2::new(500, 0) => Glyph::coloured('+', Colour::Cyan),
+            Some(Ground::Settled) => Glyph::coloured('~', Colour::Blue),
+            Some(Ground::Flowing) => Glyph::coloured('|', Colour::Cyan),
+            None => Glyph::plain('.'),
+        }
     }
 }
 
-fn parse_line(line: &str) -> Option<HashSet<IVec2>> {
+fn parse_line(line: &str) -> Option<Vec<IVec2>> {
     let (left, right) = line.split_once(", ")?;
-    let mut clay = HashSet::new();
+    let mut clay = Vec::new();
 
     if &left[0..1] == "x" {
         let x: i32 = left[2..].parse().ok()?;
@@ -128,7 +149,7 @@ fn parse_line(line: &str) -> Option<HashSet<IVec2>> {
         let (y_min, y_max) = right[2..].split_once("..")?;
 
         for y in y_min.parse::<i32>().ok()?..=y_max.parse::<i32>().ok()? {
-            clay.insert(IVec2::new(x, y));
+            clay.push(IVec2::new(x, y));
         }
     } else {
         let y: i32 = left[2..].parse().ok()?;
@@ -136,31 +157,25 @@ fn parse_line(line: &str) -> Option<HashSet<IVec2>> {
         let (x_min, x_max) = right[2..].split_once("..")?;
 
         for x in x_min.parse::<i32>().ok()?..=x_max.parse::<i32>().ok()? {
-            clay.insert(IVec2::new(x, y));
+            clay.push(IVec2::new(x, y));
         }
     }
 
     Some(clay)
 }
 
-fn parse(input: &str) -> Option<GroundSlice> {
-    Some(GroundSlice::new(
-        input.lines().filter_map(parse_line).flatten().collect(),
-    ))
+fn parse<'a>(input: &str) -> Option<GroundSlice<'a>> {
+    GroundSlice::new(input.lines().filter_map(parse_line).flatten())
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
+pub fn part_one(input: &str, sink: Option<&mut FrameSink>) -> Option<usize> {
     let mut slice = parse(input)?;
+    slice.sink = sink;
 
     slice.fill(IVec2::new(500, 0), IVec2::new(0, 1));
+    slice.show();
 
-    Some(
-        slice
-            .flowing
-            .union(&slice.settled)
-            .filter(|x| in_range(x.y, slice.min_y, slice.max_y))
-            .count(),
-    )
+    Some(slice.count(|cell| cell != Ground::Clay))
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
@@ -168,13 +183,7 @@ pub fn part_two(input: &str) -> Option<usize> {
 
     slice.fill(IVec2::new(500, 0), IVec2::new(0, 1));
 
-    Some(
-        slice
-            .settled
-            .iter()
-            .filter(|x| in_range(x.y, slice.min_y, slice.max_y))
-            .count(),
-    )
+    Some(slice.count(|cell| cell == Ground::Settled))
 }
 
 #[cfg(test)]
@@ -183,7 +192,7 @@ mod tests {
 
     #[test]
     fn test_part_one() {
-        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
+        let result = part_one(&advent_of_code::template::read_file("examples", DAY), None);
         assert_eq!(result, Some(57));
     }
 
@@ -1,8 +1,9 @@
-use std::collections::HashMap;
-
+use advent_of_code::grid::Bounds;
+use advent_of_code::render::{Colour, FrameSink, Glyph, Renderer};
+use advent_of_code::Grid;
 use glam::IVec2;
 
-advent_of_code::solution!(18);
+advent_of_code::solution!(18, visualize 1);
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum AreaType {
@@ -11,170 +12,81 @@ enum AreaType {
     Lumberyard,
 }
 
-#[derive(Debug, Clone)]
-struct Area {
-    area_type: AreaType,
-    neighbors: Vec<IVec2>,
-}
-
-impl Area {
-    fn new(area_type: AreaType, neighbors: Vec<IVec2>) -> Self {
-        Self {
-            area_type,
-            neighbors,
-        }
-    }
-}
-
 struct Map {
-    data: HashMap<IVec2, Area>,
-    width: usize,
-    height: usize,
+    areas: Grid<AreaType>,
 }
 
 impl Map {
-    fn new(data: HashMap<IVec2, Area>, width: usize, height: usize) -> Self {
-        Self {
-            data,
-            width,
-            height,
-        }
-    }
-
-    #[allow(dead_code)]
-    fn top_row(&self) -> String {
-        let mut row = String::new();
-
-        for x in 0..self.width {
-            let area = self.data.get(&IVec2::new(x as i32, 0)).unwrap();
-
-            row.push(match area.area_type {
-                AreaType::Open => '.',
-                AreaType::Woods => '|',
-                AreaType::Lumberyard => '#',
-            });
-        }
-
-        row
-    }
-
-    #[allow(dead_code)]
-    fn draw(&self) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let area = self.data.get(&IVec2::new(x as i32, y as i32)).unwrap();
-
-                match area.area_type {
-                    AreaType::Open => print!("."),
-                    AreaType::Woods => print!("|"),
-                    AreaType::Lumberyard => print!("#"),
-                }
-            }
-
-            println!();
-        }
-
-        println!();
-    }
-
     fn tick(&self) -> Map {
-        let mut next = self.data.clone();
-
-        for (pos, data) in self.data.iter() {
-            let neighbors = data
-                .neighbors
-                .iter()
-                .map(|n| self.data.get(n).unwrap().area_type)
+        let areas = self.areas.map(|pos, &area_type| {
+            let neighbors = self
+                .areas
+                .neighbors8(pos)
+                .map(|n| self.areas[n])
                 .collect::<Vec<_>>();
-
-            if data.area_type == AreaType::Open
-                && neighbors.iter().filter(|&a| *a == AreaType::Woods).count() >= 3
-            {
-                next.get_mut(pos).unwrap().area_type = AreaType::Woods;
-            }
-
-            if data.area_type == AreaType::Woods
-                && neighbors
-                    .iter()
-                    .filter(|&a| *a == AreaType::Lumberyard)
-                    .count()
-                    >= 3
-            {
-                next.get_mut(pos).unwrap().area_type = AreaType::Lumberyard;
-            }
-
-            if data.area_type == AreaType::Lumberyard
-                && (!neighbors.iter().any(|a| *a == AreaType::Lumberyard)
-                    || !neighbors.iter().any(|a| *a == AreaType::Woods))
-            {
-                next.get_mut(pos).unwrap().area_type = AreaType::Open;
+            let count = |wanted| neighbors.iter().filter(|&&a| a == wanted).count();
+
+            match area_type {
+                AreaType::Open if count(AreaType::Woods) >= 3 => AreaType::Woods,
+                AreaType::Woods if count(AreaType::Lumberyard) >= 3 => AreaType::Lumberyard,
+                AreaType::Lumberyard
+                    if !neighbors.contains(&AreaType::Lumberyard)
+                        || !neighbors.contains(&AreaType::Woods) =>
+                {
+                    AreaType::Open
+                }
+                _ => area_type,
             }
-        }
+        });
 
-        Map::new(next, self.width, self.height)
+        Map { areas }
     }
 
     fn resource_count(&self, area_type: AreaType) -> usize {
-        self.data
-            .iter()
-            .filter(|(_, area)| area.area_type == area_type)
-            .count()
+        self.areas.values().filter(|&&a| a == area_type).count()
     }
 }
 
-fn parse(input: &str) -> Option<Map> {
-    let mut map = HashMap::new();
-    let max_y = input.lines().count();
-    let max_x = input.lines().next().unwrap().len();
-
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            let area_type = match c {
-                '.' => AreaType::Open,
-                '|' => AreaType::Woods,
-                '#' => AreaType::Lumberyard,
-                _ => panic!("Invalid area type: {c}"),
-            };
-
-            let pos = IVec2::new(x as i32, y as i32);
-
-            let mut neighbors = Vec::new();
-
-            for y in pos.y - 1..=pos.y + 1 {
-                for x in pos.x - 1..=pos.x + 1 {
-                    if x < 0
-                        || y < 0
-                        || x >= max_x as i32
-                        || y >= max_y as i32
-                        || (x == pos.x && y == pos.y)
-                    {
-                        continue;
-                    }
-
-                    neighbors.push(IVec2::new(x, y));
-                }
-            }
+impl Renderer for Map {
+    fn bounds(&self) -> Option<Bounds> {
+        self.areas.bounds()
+    }
 
-            map.insert(pos, Area::new(area_type, neighbors));
+    fn glyph(&self, pos: IVec2) -> Glyph {
+        match self.areas.get(pos) {
+            Some(AreaType::Open) => Glyph::plain('.'),
+            Some(AreaType::Woods) => Glyph::coloured('|', Colour::Green),
+            Some(AreaType::Lumberyard) => Glyph::coloured('#', Colour::Yellow),
+            None => Glyph::plain(' '),
         }
     }
+}
 
-    Some(Map::new(map, max_x, max_y))
+fn parse(input: &str) -> Option<Map> {
+    let areas = Grid::parse_dense(input, |c| match c {
+        '.' => Some(AreaType::Open),
+        '|' => Some(AreaType::Woods),
+        '#' => Some(AreaType::Lumberyard),
+        _ => None,
+    })
+    .ok()?;
+
+    Some(Map { areas })
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
+pub fn part_one(input: &str, mut sink: Option<&mut FrameSink>) -> Option<usize> {
     let mut map = parse(input)?;
 
-    //println!("Initial state:");
-    //map.draw();
-    //println!();
+    if let Some(sink) = sink.as_mut() {
+        sink.emit(&map);
+    }
 
     for _ in 0..10 {
         map = map.tick();
 
-        //println!("After {} minute:", i + 1);
-        //map.draw();
-        //println!();
+        if let Some(sink) = sink.as_mut() {
+            sink.emit(&map);
+        }
     }
 
     Some(map.resource_count(AreaType::Woods) * map.resource_count(AreaType::Lumberyard))
@@ -198,7 +110,7 @@ mod tests {
 
     #[test]
     fn test_part_one() {
-        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
+        let result = part_one(&advent_of_code::template::read_file("examples", DAY), None);
         assert_eq!(result, Some(1147));
     }
 }
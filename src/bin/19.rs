@@ -1,43 +1,56 @@
-use advent_of_code::{parse_computer_program, Computer};
+use advent_of_code::{parse_computer_program, Computer, ComputerError};
 
 advent_of_code::solution!(19);
 
-pub fn part_one(input: &str) -> Option<usize> {
+fn solve(input: &str, r0: usize) -> Result<usize, ComputerError> {
     let (ip_register, program) = parse_computer_program(input)?;
-    let mut computer = Computer::new(ip_register, program);
+    let mut computer = Computer::new(ip_register, program)
+        .with_cycle_detection()
+        .with_idioms();
 
-    computer.run();
+    computer.registers[0] = r0;
+    computer.run()?;
 
-    Some(computer.registers[0])
+    Ok(computer.registers[0])
 }
 
-pub fn part_two(input: &str) -> Option<usize> {
-    let (ip_register, program) = parse_computer_program(input)?;
-    let mut computer = Computer::new(ip_register, program);
-
-    computer.registers[0] = 1;
-
-    loop {
-        computer.tick();
+pub fn part_one(input: &str) -> Result<usize, ComputerError> {
+    solve(input, 0)
+}
 
-        if computer.ip == 2 {
-            return Some(
-                computer.registers[2]
-                    + (1..=computer.registers[2] / 2)
-                        .filter(|x| computer.registers[2] % x == 0)
-                        .sum::<usize>(),
-            );
-        }
-    }
+pub fn part_two(input: &str) -> Result<usize, ComputerError> {
+    solve(input, 1)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use advent_of_code::assembler::shuffle_registers;
+    use rstest::rstest;
 
     #[test]
     fn test_part_one() {
         let result = part_one(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, Some(6));
+        assert_eq!(result, Ok(6));
+    }
+
+    #[test]
+    fn test_part_two() {
+        let result = part_two(&advent_of_code::template::read_file_part(
+            "examples", DAY, 2,
+        ));
+        assert_eq!(result, Ok(48279));
+    }
+
+    #[rstest]
+    #[case([1, 2, 3, 4, 5])]
+    #[case([5, 4, 3, 2, 1])]
+    #[case([2, 3, 4, 5, 1])]
+    #[case([4, 1, 5, 2, 3])]
+    fn test_shuffled_registers(#[case] order: [usize; 5]) {
+        let input = advent_of_code::template::read_file_part("examples", DAY, 2);
+        let input = shuffle_registers(&input, order).unwrap();
+        assert_eq!(part_one(&input), Ok(91));
+        assert_eq!(part_two(&input), Ok(48279));
     }
 }
@@ -1,22 +1,24 @@
+use std::collections::HashMap;
+
+use advent_of_code::Grid;
+use glam::IVec2;
+use itertools::Itertools;
 use pathfinding::prelude::dijkstra_all;
-use std::collections::{BTreeMap, BTreeSet};
 
 advent_of_code::solution!(20);
 
-type Point = (i32, i32);
-
 fn explore(
-    map: &mut BTreeMap<Point, BTreeSet<Point>>,
-    start: Point,
+    map: &mut Grid<Vec<IVec2>>,
+    start: IVec2,
     input: &[u8],
     index: &mut usize,
-) -> Vec<Point> {
+) -> Vec<IVec2> {
     let mut exits = vec![start];
     loop {
         match input[*index] {
             b'|' | b')' | b'$' => return exits,
             b'(' => {
-                let mut new_exits = BTreeSet::new();
+                let mut new_exits = Vec::new();
                 while input[*index] != b')' {
                     let old_index = *index;
                     new_exits.extend(exits.iter().flat_map(|pos| {
@@ -24,14 +26,17 @@ fn explore(
                         explore(map, *pos, input, index)
                     }));
                 }
-                exits = new_exits.into_iter().collect();
+                exits = new_exits.into_iter().unique().collect();
             }
             dir => {
                 let dir = usize::from((dir ^ (dir >> 2)) & 3);
-                let (dx, dy) = ([1, 0, -1, 0][dir], [0, -1, 0, 1][dir]);
+                let step = [IVec2::X, IVec2::NEG_Y, IVec2::NEG_X, IVec2::Y][dir];
                 for pos in &mut exits {
-                    let newpos = (pos.0 + dx, pos.1 + dy);
-                    map.entry(*pos).or_default().insert(newpos);
+                    let newpos = *pos + step;
+                    let doors = map.entry_or_default(*pos);
+                    if !doors.contains(&newpos) {
+                        doors.push(newpos);
+                    }
                     *pos = newpos;
                 }
             }
@@ -40,32 +45,29 @@ fn explore(
     }
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
-    let mut map = BTreeMap::new();
+fn distances(input: &str) -> HashMap<IVec2, (IVec2, usize)> {
+    let mut map = Grid::sparse();
 
-    explore(&mut map, (0, 0), input.as_bytes(), &mut 1);
+    explore(&mut map, IVec2::ZERO, input.as_bytes(), &mut 1);
 
-    let map = dijkstra_all(&(0, 0), |pos| {
+    dijkstra_all(&IVec2::ZERO, |&pos| {
         map.get(pos)
             .into_iter()
             .flat_map(|neighbours| neighbours.iter().map(|n| (*n, 1)))
-    });
+    })
+}
 
-    Some(map.values().map(|(_, c)| *c).max().unwrap())
+pub fn part_one(input: &str) -> Option<usize> {
+    distances(input).values().map(|(_, c)| *c).max()
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
-    let mut map = BTreeMap::new();
-
-    explore(&mut map, (0, 0), input.as_bytes(), &mut 1);
-
-    let map = dijkstra_all(&(0, 0), |pos| {
-        map.get(pos)
-            .into_iter()
-            .flat_map(|neighbours| neighbours.iter().map(|n| (*n, 1)))
-    });
-
-    Some(map.values().filter(|&(_, c)| *c >= 1000).count())
+    Some(
+        distances(input)
+            .values()
+            .filter(|&(_, c)| *c >= 1000)
+            .count(),
+    )
 }
 
 #[cfg(test)]
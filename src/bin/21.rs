@@ -1,54 +1,121 @@
-use advent_of_code::{parse_computer_program, Computer};
+use std::fmt;
+use std::time::Duration;
+
+use advent_of_code::analysis::halting_compare;
+use advent_of_code::{
+    parse_computer_program, Computer, ComputerError, ParseError, RunError, StopReason,
+};
 
 advent_of_code::solution!(21);
 
-pub fn part_one(input: &str) -> Option<usize> {
-    let (ip_register, program) = parse_computer_program(input)?;
-    let mut computer = Computer::new(ip_register, program);
+/// Real inputs repeat their compared values well within this, so a program that keeps running
+/// past it is not one we can solve.
+const TIME_LIMIT: Duration = Duration::from_secs(60);
 
-    let mut seen = Vec::new();
-    loop {
-        computer.tick();
-        if computer.ip == 28 {
-            let value = computer.registers[5];
-            if seen.contains(&value) {
-                return Some(*seen.first().unwrap());
-            }
-            seen.push(value);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HaltingError {
+    Computer(ComputerError),
+    /// No compare against r0 decides whether the program halts.
+    NoHaltingCompare,
+    /// The program halted before comparing anything against r0.
+    NothingCompared,
+}
+
+impl fmt::Display for HaltingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HaltingError::Computer(e) => write!(f, "{e}"),
+            HaltingError::NoHaltingCompare => write!(f, "no compare against r0 halts the program"),
+            HaltingError::NothingCompared => write!(f, "nothing was compared against r0"),
         }
     }
 }
 
-pub fn part_two(input: &str) -> Option<usize> {
+impl From<ParseError> for HaltingError {
+    fn from(e: ParseError) -> Self {
+        HaltingError::Computer(e.into())
+    }
+}
+
+impl From<RunError> for HaltingError {
+    fn from(e: RunError) -> Self {
+        HaltingError::Computer(e.into())
+    }
+}
+
+/// The values r0 could be compared against, in the order the program compares them, up to the
+/// first repeat.
+fn halting_values(input: &str) -> Result<Vec<usize>, HaltingError> {
     let (ip_register, program) = parse_computer_program(input)?;
-    let mut computer = Computer::new(ip_register, program);
+    let (address, register) =
+        halting_compare(ip_register, &program).ok_or(HaltingError::NoHaltingCompare)?;
+    let mut computer = Computer::new(ip_register, program)
+        .with_idioms()
+        .with_time_limit(TIME_LIMIT);
+
+    computer.add_breakpoint(address);
+    let compiled = computer.compile();
 
     let mut seen = Vec::new();
-    loop {
-        computer.tick();
-        if computer.ip == 28 {
-            let value = computer.registers[5];
-            if seen.contains(&value) {
-                return Some(*seen.last().unwrap());
-            }
-            seen.push(value);
+    while let StopReason::Breakpoint(_) = computer.run_compiled(&compiled)? {
+        let value = computer.registers[register];
+        if seen.contains(&value) {
+            break;
         }
+        seen.push(value);
     }
+
+    Ok(seen)
+}
+
+pub fn part_one(input: &str) -> Result<usize, HaltingError> {
+    halting_values(input)?
+        .first()
+        .copied()
+        .ok_or(HaltingError::NothingCompared)
+}
+
+pub fn part_two(input: &str) -> Result<usize, HaltingError> {
+    halting_values(input)?
+        .last()
+        .copied()
+        .ok_or(HaltingError::NothingCompared)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use advent_of_code::assembler::shuffle_registers;
+    use rstest::rstest;
 
     #[test]
     fn test_part_one() {
         let result = part_one(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, None);
+        assert!(matches!(result, Err(HaltingError::Computer(_))));
     }
 
     #[test]
     fn test_part_two() {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, None);
+        assert!(matches!(result, Err(HaltingError::Computer(_))));
+    }
+
+    #[test]
+    fn test_no_halting_compare() {
+        assert_eq!(
+            part_one("#ip 0\nseti 5 0 1"),
+            Err(HaltingError::NoHaltingCompare)
+        );
+    }
+
+    #[rstest]
+    #[case([1, 2, 3, 4, 5])]
+    #[case([5, 4, 3, 2, 1])]
+    #[case([2, 3, 4, 5, 1])]
+    fn test_shuffled_registers(#[case] order: [usize; 5]) {
+        let input = advent_of_code::template::read_file_part("examples", DAY, 2);
+        let input = shuffle_registers(&input, order).unwrap();
+        assert_eq!(part_one(&input), Ok(38110));
+        assert_eq!(part_two(&input), Ok(44178));
     }
 }
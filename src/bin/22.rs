@@ -1,36 +1,29 @@
-use pathfinding::prelude::{astar, Matrix};
-use std::collections::HashMap;
-
+use advent_of_code::grid::Bounds;
+use advent_of_code::Grid;
 use glam::IVec2;
+use pathfinding::prelude::astar;
 
 advent_of_code::solution!(22);
 
-fn build_region_map(depth: usize, bounds: &IVec2, target: &IVec2) -> HashMap<IVec2, usize> {
-    let mut erosion_level_map: HashMap<IVec2, usize> = HashMap::new();
-    let mut region_map: HashMap<IVec2, usize> = HashMap::new();
-
-    for y in 0..=bounds.y {
-        for x in 0..=bounds.x {
-            let geologic_index = if (x == 0 && y == 0) || (x == target.x && y == target.y) {
-                0
-            } else if y == 0 {
-                x as usize * 16807
-            } else if x == 0 {
-                y as usize * 48271
-            } else {
-                erosion_level_map.get(&IVec2::new(x - 1, y)).unwrap()
-                    * erosion_level_map.get(&IVec2::new(x, y - 1)).unwrap()
-            };
-
-            let erosion_level = (geologic_index + depth).rem_euclid(20183);
-
-            erosion_level_map.insert(IVec2::new(x, y), erosion_level);
-
-            region_map.insert(IVec2::new(x, y), erosion_level.rem_euclid(3));
-        }
+fn build_region_map(depth: usize, corner: IVec2, target: IVec2) -> Grid<usize> {
+    let bounds = Bounds::new(IVec2::ZERO, corner);
+    let mut erosion_levels = Grid::dense(bounds, 0);
+
+    for pos in bounds.positions() {
+        let geologic_index = if pos == IVec2::ZERO || pos == target {
+            0
+        } else if pos.y == 0 {
+            pos.x as usize * 16807
+        } else if pos.x == 0 {
+            pos.y as usize * 48271
+        } else {
+            erosion_levels[pos - IVec2::X] * erosion_levels[pos - IVec2::Y]
+        };
+
+        erosion_levels[pos] = (geologic_index + depth).rem_euclid(20183);
     }
 
-    region_map
+    erosion_levels.map(|_, erosion_level| erosion_level.rem_euclid(3))
 }
 
 fn parse(input: &str) -> Option<(usize, IVec2)> {
@@ -45,7 +38,7 @@ fn parse(input: &str) -> Option<(usize, IVec2)> {
 pub fn part_one(input: &str) -> Option<usize> {
     let (depth, target) = parse(input)?;
 
-    let region_map = build_region_map(depth, &target, &target);
+    let region_map = build_region_map(depth, target, target);
 
     Some(region_map.values().sum())
 }
@@ -53,22 +46,7 @@ pub fn part_one(input: &str) -> Option<usize> {
 pub fn part_two(input: &str) -> Option<usize> {
     let (depth, target) = parse(input)?;
 
-    let corner = target + IVec2::new(100, 100);
-
-    let region_map = build_region_map(depth, &corner, &target);
-
-    let mut matrix = Matrix::new(corner.x as usize + 1, corner.y as usize + 1, 0);
-
-    for y in 0..=corner.y as usize {
-        for x in 0..=corner.x as usize {
-            matrix[(x, y)] = match region_map.get(&IVec2::new(x as i32, y as i32)) {
-                Some(0) => 0,
-                Some(1) => 1,
-                Some(2) => 2,
-                _ => panic!("Unknown region type"),
-            };
-        }
-    }
+    let region_map = build_region_map(depth, target + IVec2::new(100, 100), target);
 
     const NEITHER: usize = 1;
     const TORCH: usize = 2;
@@ -77,17 +55,17 @@ pub fn part_two(input: &str) -> Option<usize> {
     const ALLOWED: [usize; 3] = [TORCH + GEAR, NEITHER + GEAR, NEITHER + TORCH];
 
     astar(
-        &((0, 0), TORCH),
-        |&((x, y), eq)| {
-            matrix
-                .neighbours((x, y), false)
-                .filter(|&(nx, ny)| ALLOWED[matrix[(nx, ny)]] & eq == eq)
-                .map(|(nx, ny)| (((nx, ny), eq), 1))
-                .chain(std::iter::once((((x, y), ALLOWED[matrix[(x, y)]] - eq), 7)))
+        &(IVec2::ZERO, TORCH),
+        |&(pos, eq)| {
+            region_map
+                .neighbors4(pos)
+                .filter(|&n| ALLOWED[region_map[n]] & eq == eq)
+                .map(|n| ((n, eq), 1))
+                .chain(std::iter::once(((pos, ALLOWED[region_map[pos]] - eq), 7)))
                 .collect::<Vec<_>>()
         },
-        |&((x, y), _)| x.abs_diff(target.x as usize) + y.abs_diff(target.y as usize),
-        |&((x, y), eq)| x == target.x as usize && y == target.y as usize && eq == TORCH,
+        |&(pos, _)| (pos.x.abs_diff(target.x) + pos.y.abs_diff(target.y)) as usize,
+        |&(pos, eq)| pos == target && eq == TORCH,
     )
     .map(|(_, cost)| cost)
 }
@@ -35,8 +35,7 @@ fn parse_group(input: &str) -> Option<Group> {
     let mut weaknesses = Vec::new();
     let mut immunities = Vec::new();
 
-    if weaknesses_immunities_str.is_some() {
-        let (weaknesses_immunities_str, more) = weaknesses_immunities_str.unwrap();
+    if let Some((weaknesses_immunities_str, more)) = weaknesses_immunities_str {
         rest = more;
 
         let weaknesses_immunities_str = weaknesses_immunities_str
@@ -0,0 +1,57 @@
+//! Runs every day in one process, linking each day's binary source as a module.
+
+#[cfg(not(feature = "dhat-heap"))]
+use advent_of_code::template::registry::{run_all, Solution};
+
+macro_rules! days {
+    ($($module:ident = $path:literal),* $(,)?) => {
+        // Each day defines its own global allocator when profiling the heap.
+        $(
+            #[cfg(not(feature = "dhat-heap"))]
+            #[path = $path]
+            mod $module;
+        )*
+
+        #[cfg(not(feature = "dhat-heap"))]
+        const SOLUTIONS: &[Solution] = &[$($module::SOLUTION),*];
+    };
+}
+
+days! {
+    day01 = "01.rs",
+    day02 = "02.rs",
+    day03 = "03.rs",
+    day04 = "04.rs",
+    day05 = "05.rs",
+    day06 = "06.rs",
+    day07 = "07.rs",
+    day08 = "08.rs",
+    day09 = "09.rs",
+    day10 = "10.rs",
+    day11 = "11.rs",
+    day12 = "12.rs",
+    day13 = "13.rs",
+    day14 = "14.rs",
+    day15 = "15.rs",
+    day16 = "16.rs",
+    day17 = "17.rs",
+    day18 = "18.rs",
+    day19 = "19.rs",
+    day20 = "20.rs",
+    day21 = "21.rs",
+    day22 = "22.rs",
+    day23 = "23.rs",
+    day24 = "24.rs",
+    day25 = "25.rs",
+}
+
+#[cfg(not(feature = "dhat-heap"))]
+fn main() {
+    run_all(SOLUTIONS);
+}
+
+#[cfg(feature = "dhat-heap")]
+fn main() {
+    eprintln!("Heap profiling is per day, use `cargo solve <day> --dhat` instead.");
+    std::process::exit(1);
+}
@@ -0,0 +1,314 @@
+use advent_of_code::disassembler::Disassembly;
+use advent_of_code::template::Day;
+use advent_of_code::trace::Profile;
+use advent_of_code::{parse_computer_program, Computer, StopReason, REGISTER_COUNT};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, process};
+
+/// Number of instructions shown on each side of the current one by `list`.
+const LIST_CONTEXT: usize = 5;
+
+const HELP: &str = "\
+commands:
+  s, step [n]         execute n instructions (default 1)
+  c, continue         run until a breakpoint, watchpoint or halt
+  b, break <addr>     set a breakpoint
+  d, delete <addr>    remove a breakpoint
+  w, watch <reg>      stop when register <reg> is written
+  u, unwatch <reg>    remove a watchpoint
+  p, print [reg]      print all registers, or a single one
+  set <reg> <value>   set a register
+  l, list             show the current instruction with surrounding disassembly
+  i, info             show ip, instruction count, breakpoints and watchpoints
+  profile             start profiling, or show the hottest instructions and loops so far
+  r, reset            restart the program with cleared registers and profile
+  h, help             show this message
+  q, quit             exit the debugger
+an empty line repeats the previous command.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Step(usize),
+    Continue,
+    Break(usize),
+    Delete(usize),
+    Watch(usize),
+    Unwatch(usize),
+    Print(Option<usize>),
+    Set(usize, usize),
+    List,
+    Info,
+    Profile,
+    Reset,
+    Help,
+    Quit,
+}
+
+fn parse_register(s: &str) -> Result<usize, String> {
+    let register = s
+        .strip_prefix('r')
+        .unwrap_or(s)
+        .parse::<usize>()
+        .map_err(|_| format!("invalid register \"{s}\""))?;
+
+    if register >= REGISTER_COUNT {
+        return Err(format!("register {register} is out of range"));
+    }
+
+    Ok(register)
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("invalid number \"{s}\""))
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+
+    let command = match words.as_slice() {
+        ["s" | "step"] => Command::Step(1),
+        ["s" | "step", n] => Command::Step(parse_number(n)?),
+        ["c" | "continue"] => Command::Continue,
+        ["b" | "break", address] => Command::Break(parse_number(address)?),
+        ["d" | "delete", address] => Command::Delete(parse_number(address)?),
+        ["w" | "watch", register] => Command::Watch(parse_register(register)?),
+        ["u" | "unwatch", register] => Command::Unwatch(parse_register(register)?),
+        ["p" | "print"] => Command::Print(None),
+        ["p" | "print", register] => Command::Print(Some(parse_register(register)?)),
+        ["set", register, value] => Command::Set(parse_register(register)?, parse_number(value)?),
+        ["l" | "list"] => Command::List,
+        ["i" | "info"] => Command::Info,
+        ["profile"] => Command::Profile,
+        ["r" | "reset"] => Command::Reset,
+        ["h" | "help"] => Command::Help,
+        ["q" | "quit"] => Command::Quit,
+        _ => {
+            return Err(format!(
+                "unknown command \"{line}\", type \"help\" for a list"
+            ))
+        }
+    };
+
+    Ok(command)
+}
+
+struct Debugger {
+    computer: Computer,
+    disassembly: Disassembly,
+}
+
+impl Debugger {
+    fn print_registers(&self) {
+        let registers = self
+            .computer
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| format!("r{idx}={value}"))
+            .collect::<Vec<_>>();
+        println!("{}", registers.join(" "));
+    }
+
+    fn print_stop(&self, reason: StopReason) {
+        match reason {
+            StopReason::Halted => println!(
+                "halted after {} instructions",
+                self.computer.instructions_executed
+            ),
+            StopReason::Breakpoint(address) => println!("breakpoint at {address}"),
+            StopReason::Watchpoint { register, address } => println!(
+                "r{register} = {} (written by {address})",
+                self.computer.registers[register]
+            ),
+            StopReason::BudgetExhausted => {}
+        }
+    }
+
+    fn list(&self) {
+        let ip = self.computer.ip;
+
+        if self.computer.is_halted() {
+            println!("ip {ip} is outside of the program");
+            return;
+        }
+
+        let from = ip.saturating_sub(LIST_CONTEXT);
+        let to = (ip + LIST_CONTEXT + 1).min(self.disassembly.lines.len());
+
+        for line in &self.disassembly.lines[from..to] {
+            let marker = if line.address == ip { "=>" } else { "  " };
+            let breakpoint = if self.computer.breakpoints.contains(&line.address) {
+                '*'
+            } else {
+                ' '
+            };
+            println!("{marker}{breakpoint}{line}");
+        }
+    }
+
+    fn execute(&mut self, command: Command) {
+        match command {
+            Command::Step(n) => {
+                for _ in 0..n {
+                    if self.computer.is_halted() {
+                        break;
+                    }
+                    if let Err(e) = self.computer.tick() {
+                        println!("{e}");
+                        break;
+                    }
+                }
+                if self.computer.is_halted() {
+                    self.print_stop(StopReason::Halted);
+                } else {
+                    self.list();
+                }
+            }
+            Command::Continue => match self.computer.run_until(None) {
+                Ok(reason) => {
+                    self.print_stop(reason);
+                    self.print_registers();
+                }
+                Err(e) => println!("{e}"),
+            },
+            Command::Break(address) => {
+                self.computer.add_breakpoint(address);
+                println!("breakpoint set at {address}");
+            }
+            Command::Delete(address) => {
+                self.computer.remove_breakpoint(address);
+                println!("breakpoint at {address} removed");
+            }
+            Command::Watch(register) => {
+                self.computer.add_watchpoint(register);
+                println!("watching r{register}");
+            }
+            Command::Unwatch(register) => {
+                self.computer.remove_watchpoint(register);
+                println!("no longer watching r{register}");
+            }
+            Command::Print(None) => self.print_registers(),
+            Command::Print(Some(register)) => {
+                println!("r{register}={}", self.computer.registers[register]);
+            }
+            Command::Set(register, value) => {
+                self.computer.registers[register] = value;
+                self.print_registers();
+            }
+            Command::List => self.list(),
+            Command::Info => {
+                println!("ip: {}", self.computer.ip);
+                println!(
+                    "instructions executed: {}",
+                    self.computer.instructions_executed
+                );
+                println!("breakpoints: {:?}", self.computer.breakpoints);
+                println!("watchpoints: {:?}", self.computer.watchpoints);
+            }
+            Command::Profile => match self.computer.profile_report() {
+                Some(report) => print!("{report}"),
+                None => {
+                    self.computer.profile = Some(Profile::new(self.computer.program.len()));
+                    println!("profiling enabled");
+                }
+            },
+            Command::Reset => {
+                self.computer.reset_program();
+                if self.computer.profile.is_some() {
+                    self.computer.profile = Some(Profile::new(self.computer.program.len()));
+                }
+                self.list();
+            }
+            Command::Help => println!("{HELP}"),
+            Command::Quit => process::exit(0),
+        }
+    }
+}
+
+/// Reads `data/inputs/NN.txt` if `arg` is a day number, or `arg` as a path otherwise.
+fn read_program(arg: &str) -> String {
+    let path = match arg.parse::<Day>() {
+        Ok(day) => Path::new("data").join("inputs").join(format!("{day}.txt")),
+        Err(_) => PathBuf::from(arg),
+    };
+
+    fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("could not read {}: {e}", path.display());
+        process::exit(1);
+    })
+}
+
+fn main() {
+    let mut args = pico_args::Arguments::from_env();
+
+    let Ok(arg) = args.free_from_str::<String>() else {
+        eprintln!("Usage: elfdbg <day | path>");
+        process::exit(1);
+    };
+
+    let (ip_register, program) = match parse_computer_program(&read_program(&arg)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{arg}: {e}");
+            process::exit(1);
+        }
+    };
+
+    let mut debugger = Debugger {
+        disassembly: Disassembly::new(ip_register, &program),
+        computer: Computer::new(ip_register, program),
+    };
+
+    println!(
+        "loaded {} instructions, ip bound to r{ip_register}. type \"help\" for commands.",
+        debugger.computer.program.len()
+    );
+    debugger.list();
+
+    let mut previous = None;
+    let stdin = io::stdin();
+
+    loop {
+        print!("(elfdbg) ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+
+        let command = if line.trim().is_empty() {
+            match previous {
+                Some(command) => Ok(command),
+                None => continue,
+            }
+        } else {
+            parse_command(line.trim())
+        };
+
+        match command {
+            Ok(command) => {
+                previous = Some(command);
+                debugger.execute(command);
+            }
+            Err(e) => println!("{e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("s"), Ok(Command::Step(1)));
+        assert_eq!(parse_command("step 10"), Ok(Command::Step(10)));
+        assert_eq!(parse_command("b 28"), Ok(Command::Break(28)));
+        assert_eq!(parse_command("p r5"), Ok(Command::Print(Some(5))));
+        assert_eq!(parse_command("set 0 1"), Ok(Command::Set(0, 1)));
+        assert!(parse_command("set r6 1").is_err());
+        assert!(parse_command("jump").is_err());
+    }
+}
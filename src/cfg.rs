@@ -0,0 +1,409 @@
+//! Control-flow graph, dominators and natural loops for ElfCode programs.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::disassembler::{flow, Disassembly, Flow};
+use crate::{OpKind, Program};
+
+/// A maximal run of instructions `start..end` that is only entered at `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl BasicBlock {
+    pub fn contains(&self, address: usize) -> bool {
+        (self.start..self.end).contains(&address)
+    }
+
+    /// Address of the instruction that hands control to the successors.
+    pub fn last(&self) -> usize {
+        self.end - 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Control runs off the end of the block into the next one.
+    FallThrough,
+    /// An unconditional write of a constant to the ip register, e.g. `seti` or `addi`.
+    Jump,
+    /// A compare-then-jump pair whose compare held.
+    Taken,
+    /// A compare-then-jump pair whose compare did not hold.
+    NotTaken,
+    /// `addr` of the ip register and a register that is not known to be a compare result.
+    /// The register is assumed to hold a 0/1 flag, giving two possible targets.
+    Relative,
+}
+
+/// An edge between two blocks, identified by their index in [`Cfg::blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A natural loop: every block in `body` can reach one of the `latches` without leaving the loop,
+/// and `header` dominates all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub header: usize,
+    pub latches: Vec<usize>,
+    pub body: BTreeSet<usize>,
+    /// Edges leaving the loop.
+    pub exits: Vec<Edge>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+    /// Blocks whose last instruction may move the ip outside of the program.
+    pub halting: Vec<usize>,
+    /// Blocks whose successors could not be determined statically.
+    pub unresolved: Vec<usize>,
+}
+
+/// Possible next addresses for the instruction at `address`, or `None` if they are unknown.
+fn successors(
+    ip_register: usize,
+    program: &Program,
+    address: usize,
+) -> Option<Vec<(usize, EdgeKind)>> {
+    match flow(ip_register, program, address) {
+        Flow::Next => Some(vec![(address + 1, EdgeKind::FallThrough)]),
+        Flow::Goto(target) => Some(vec![(target, EdgeKind::Jump)]),
+        Flow::Branch {
+            taken, fallthrough, ..
+        } => Some(vec![
+            (fallthrough, EdgeKind::NotTaken),
+            (taken, EdgeKind::Taken),
+        ]),
+        Flow::Computed => {
+            let ins = &program[address];
+            let relative = ins.op == OpKind::Addr
+                && (ins.input_a == ip_register || ins.input_b == ip_register);
+
+            relative.then(|| {
+                vec![
+                    (address + 1, EdgeKind::Relative),
+                    (address + 2, EdgeKind::Relative),
+                ]
+            })
+        }
+    }
+}
+
+impl Cfg {
+    pub fn new(ip_register: usize, program: &Program) -> Self {
+        let len = program.len();
+        let mut leaders = BTreeSet::from([0]);
+
+        for address in 0..len {
+            match successors(ip_register, program, address) {
+                Some(next) if next == [(address + 1, EdgeKind::FallThrough)] => {}
+                Some(next) => {
+                    leaders.insert(address + 1);
+                    leaders.extend(next.into_iter().map(|(target, _)| target));
+                }
+                None => {
+                    leaders.insert(address + 1);
+                }
+            }
+        }
+
+        let starts = leaders.into_iter().filter(|&l| l < len).collect::<Vec<_>>();
+        let blocks = starts
+            .iter()
+            .enumerate()
+            .map(|(idx, &start)| BasicBlock {
+                start,
+                end: starts.get(idx + 1).copied().unwrap_or(len),
+            })
+            .collect::<Vec<_>>();
+
+        let mut cfg = Self {
+            blocks,
+            edges: Vec::new(),
+            halting: Vec::new(),
+            unresolved: Vec::new(),
+        };
+
+        for from in 0..cfg.blocks.len() {
+            let last = cfg.blocks[from].last();
+
+            let Some(next) = successors(ip_register, program, last) else {
+                cfg.unresolved.push(from);
+                continue;
+            };
+
+            for (target, kind) in next {
+                match cfg.block_at(target) {
+                    Some(to) => cfg.edges.push(Edge { from, to, kind }),
+                    None if !cfg.halting.contains(&from) => cfg.halting.push(from),
+                    None => {}
+                }
+            }
+        }
+
+        cfg
+    }
+
+    /// Index of the block containing `address`.
+    pub fn block_at(&self, address: usize) -> Option<usize> {
+        self.blocks.iter().position(|block| block.contains(address))
+    }
+
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.from == block)
+            .map(|edge| edge.to)
+    }
+
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.to == block)
+            .map(|edge| edge.from)
+    }
+
+    /// Blocks reachable from the entry block.
+    pub fn reachable(&self) -> BTreeSet<usize> {
+        let mut seen = BTreeSet::new();
+        let mut to_visit = vec![0];
+
+        while let Some(block) = to_visit.pop() {
+            if seen.insert(block) {
+                to_visit.extend(self.successors(block));
+            }
+        }
+
+        seen
+    }
+
+    /// The set of blocks dominating each block. Unreachable blocks have no dominators.
+    pub fn dominators(&self) -> Vec<BTreeSet<usize>> {
+        let reachable = self.reachable();
+        let mut dominators = (0..self.blocks.len())
+            .map(|block| {
+                if block == 0 {
+                    BTreeSet::from([0])
+                } else if reachable.contains(&block) {
+                    reachable.clone()
+                } else {
+                    BTreeSet::new()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for &block in reachable.iter().skip(1) {
+                let mut next = self
+                    .predecessors(block)
+                    .filter(|p| reachable.contains(p))
+                    .map(|p| dominators[p].clone())
+                    .reduce(|acc, d| acc.intersection(&d).copied().collect())
+                    .unwrap_or_default();
+                next.insert(block);
+
+                if next != dominators[block] {
+                    dominators[block] = next;
+                    changed = true;
+                }
+            }
+        }
+
+        dominators
+    }
+
+    /// Natural loops, one per header, ordered by header address.
+    pub fn loops(&self) -> Vec<Loop> {
+        let dominators = self.dominators();
+        let mut loops: Vec<Loop> = Vec::new();
+
+        for edge in &self.edges {
+            if !dominators[edge.from].contains(&edge.to) {
+                continue;
+            }
+
+            let header = edge.to;
+            let mut body = BTreeSet::from([header]);
+            let mut to_visit = vec![edge.from];
+
+            while let Some(block) = to_visit.pop() {
+                if body.insert(block) {
+                    to_visit.extend(self.predecessors(block));
+                }
+            }
+
+            match loops.iter_mut().find(|l| l.header == header) {
+                Some(existing) => {
+                    existing.latches.push(edge.from);
+                    existing.body.extend(body);
+                }
+                None => loops.push(Loop {
+                    header,
+                    latches: vec![edge.from],
+                    body,
+                    exits: Vec::new(),
+                }),
+            }
+        }
+
+        for l in loops.iter_mut() {
+            l.exits = self
+                .edges
+                .iter()
+                .filter(|edge| l.body.contains(&edge.from) && !l.body.contains(&edge.to))
+                .copied()
+                .collect();
+        }
+
+        loops.sort_by_key(|l| self.blocks[l.header].start);
+        loops
+    }
+
+    /// Loops that do not contain any other loop.
+    pub fn innermost_loops(&self) -> Vec<Loop> {
+        let loops = self.loops();
+
+        loops
+            .iter()
+            .filter(|l| {
+                !loops
+                    .iter()
+                    .any(|other| other.header != l.header && l.body.contains(&other.header))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Renders the graph in Graphviz DOT format, labelling each block with its disassembly.
+    pub fn to_dot(&self, ip_register: usize, program: &Program) -> String {
+        let disassembly = Disassembly::new(ip_register, program);
+        let mut dot = String::from("digraph program {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (idx, block) in self.blocks.iter().enumerate() {
+            let label = disassembly.lines[block.start..block.end]
+                .iter()
+                .map(|line| format!("{line}\\l").replace('"', "\\\""))
+                .collect::<String>();
+            let _ = writeln!(dot, "    b{idx} [label=\"L{}:\\l{label}\"];", block.start);
+        }
+
+        if !self.halting.is_empty() {
+            dot.push_str("    halt [shape=doublecircle];\n");
+        }
+
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::FallThrough => "",
+                EdgeKind::Jump => " [style=bold]",
+                EdgeKind::Taken => " [label=\"taken\", color=green]",
+                EdgeKind::NotTaken => " [label=\"not taken\", color=red]",
+                EdgeKind::Relative => " [style=dashed]",
+            };
+            let _ = writeln!(dot, "    b{} -> b{}{style};", edge.from, edge.to);
+        }
+
+        for block in &self.halting {
+            let _ = writeln!(dot, "    b{block} -> halt;");
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_computer_program;
+    use crate::template::read_file_part;
+
+    fn load(day: u8) -> (usize, Program) {
+        let day = crate::template::Day::new(day).unwrap();
+        parse_computer_program(&read_file_part("examples", day, 2)).unwrap()
+    }
+
+    fn loop_addresses(cfg: &Cfg, l: &Loop) -> Vec<usize> {
+        l.body.iter().map(|&b| cfg.blocks[b].start).collect()
+    }
+
+    #[test]
+    fn splits_blocks_and_edges() {
+        let (ip_register, program) = load(19);
+        let cfg = Cfg::new(ip_register, &program);
+
+        let starts = cfg.blocks.iter().map(|b| b.start).collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![0, 1, 2, 3, 6, 7, 8, 11, 12, 15, 16, 17, 20, 21]
+        );
+
+        let block = |address| cfg.block_at(address).unwrap();
+        assert!(cfg.edges.contains(&Edge {
+            from: block(3),
+            to: block(7),
+            kind: EdgeKind::Taken
+        }));
+        assert!(cfg.edges.contains(&Edge {
+            from: block(17),
+            to: block(21),
+            kind: EdgeKind::Relative
+        }));
+        assert_eq!(cfg.halting, vec![block(16)]);
+        assert!(cfg.unresolved.is_empty());
+    }
+
+    #[test]
+    fn finds_nested_loops() {
+        let (ip_register, program) = load(19);
+        let cfg = Cfg::new(ip_register, &program);
+
+        let loops = cfg.loops();
+        let headers = loops
+            .iter()
+            .map(|l| cfg.blocks[l.header].start)
+            .collect::<Vec<_>>();
+        assert_eq!(headers, vec![2, 3]);
+
+        let inner = cfg.innermost_loops();
+        assert_eq!(inner.len(), 1);
+        assert_eq!(loop_addresses(&cfg, &inner[0]), vec![3, 6, 7, 8, 11]);
+        assert_eq!(inner[0].exits.len(), 1);
+        assert_eq!(cfg.blocks[inner[0].exits[0].to].start, 12);
+    }
+
+    #[test]
+    fn finds_exit_check() {
+        let (ip_register, program) = load(21);
+        let cfg = Cfg::new(ip_register, &program);
+
+        assert_eq!(cfg.halting.len(), 1);
+        assert_eq!(
+            cfg.blocks[cfg.halting[0]],
+            BasicBlock { start: 23, end: 25 }
+        );
+    }
+
+    #[test]
+    fn exports_dot() {
+        let (ip_register, program) = load(19);
+        let dot = Cfg::new(ip_register, &program).to_dot(ip_register, &program);
+
+        assert!(dot.starts_with("digraph program {\n"));
+        assert!(dot.contains("b0 -> b11 [style=bold];"));
+        assert!(dot.contains("b10 -> halt;"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
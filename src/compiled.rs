@@ -0,0 +1,554 @@
+//! Block-threaded execution of ElfCode programs, a faster alternative to [`Machine::tick`].
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::disassembler::{flow, Flow};
+use crate::{Fault, Machine, OpKind, Operand, Program, RunError, StopReason, Word};
+
+/// An input with its register bounds already checked and reads of the ip register folded to the
+/// instruction's address.
+#[derive(Debug, Clone, Copy)]
+enum Source<W> {
+    Reg(usize),
+    Imm(W),
+}
+
+/// An instruction with its inputs pre-bound.
+#[derive(Debug, Clone, Copy)]
+struct Op<W> {
+    kind: OpKind,
+    a: Source<W>,
+    b: Source<W>,
+    output: usize,
+}
+
+impl<W: Word> Op<W> {
+    /// Binds the instruction at `address`. Invalid registers and immediates that do not fit in a
+    /// register are turned into the fault the interpreter would report when executing it.
+    fn new<const N: usize>(
+        ip_register: Option<usize>,
+        address: usize,
+        program: &Program,
+    ) -> Result<Self, Fault> {
+        let ins = &program[address];
+
+        // The interpreter faults before executing anything if the ip register does not exist.
+        if let Some(r) = ip_register.filter(|&r| r >= N) {
+            return Err(Fault::RegisterOutOfRange(r));
+        }
+
+        let source = |operand: Operand, raw: usize| match operand {
+            Operand::Register if raw >= N => Err(Fault::RegisterOutOfRange(raw)),
+            Operand::Register if Some(raw) == ip_register => W::from_usize(address)
+                .map(Source::Imm)
+                .ok_or(Fault::Overflow),
+            Operand::Register => Ok(Source::Reg(raw)),
+            Operand::Immediate => W::from_usize(raw).map(Source::Imm).ok_or(Fault::Overflow),
+            Operand::Ignored => Ok(Source::Imm(W::ZERO)),
+        };
+
+        let a = source(ins.op.operand_a(), ins.input_a)?;
+        let b = source(ins.op.operand_b(), ins.input_b)?;
+
+        if ins.output >= N {
+            return Err(Fault::RegisterOutOfRange(ins.output));
+        }
+
+        Ok(Self {
+            kind: ins.op,
+            a,
+            b,
+            output: ins.output,
+        })
+    }
+
+    fn is_constant(&self) -> bool {
+        matches!((self.a, self.b), (Source::Imm(_), Source::Imm(_)))
+    }
+
+    #[inline(always)]
+    fn apply<const N: usize>(&self, registers: &mut [W; N]) -> Result<(), Fault> {
+        let get = |source: Source<W>| match source {
+            Source::Reg(r) => registers[r],
+            Source::Imm(v) => v,
+        };
+        let (a, b) = (get(self.a), get(self.b));
+        let flag = |b: bool| if b { W::ONE } else { W::ZERO };
+
+        registers[self.output] = match self.kind {
+            OpKind::Addr | OpKind::Addi => a.add(b).ok_or(Fault::Overflow)?,
+            OpKind::Mulr | OpKind::Muli => a.mul(b).ok_or(Fault::Overflow)?,
+            OpKind::Banr | OpKind::Bani => a.bitand(b),
+            OpKind::Borr | OpKind::Bori => a.bitor(b),
+            OpKind::Setr | OpKind::Seti => a,
+            OpKind::Gtir | OpKind::Gtri | OpKind::Gtrr => flag(a > b),
+            OpKind::Eqir | OpKind::Eqri | OpKind::Eqrr => flag(a == b),
+        };
+
+        Ok(())
+    }
+}
+
+/// How control leaves a run of instructions ending at a given address.
+#[derive(Debug, Clone, Copy)]
+enum Exit<W> {
+    /// The instruction does not write the ip register, control continues at the next address.
+    Next,
+    /// The instruction writes a constant to the ip register.
+    Jump(usize),
+    /// The instruction is the compare of a compare-then-jump pair. Both instructions are
+    /// executed by the run, each target comes with the value left in the ip register.
+    Branch {
+        ip_register: usize,
+        flag: usize,
+        taken: (W, usize),
+        fallthrough: (W, usize),
+    },
+    /// The target depends on a register value and is read back from the ip register.
+    Computed(usize),
+}
+
+/// A [`Program`] compiled for a [`Machine`] with `N` registers of type `W`.
+///
+/// Straight-line runs of instructions execute without writing the ip register, which is only
+/// updated once control leaves the run. Jumps to constant addresses and compare-then-jump pairs
+/// are resolved at compile time. Register files and instruction counts match those of the
+/// interpreter at every point where control returns to the caller.
+///
+/// Runs stop at breakpoints and at the start of superinstructions, so a compiled program is only
+/// valid for the machine state it was compiled from, see [`Machine::run_compiled`].
+#[derive(Debug, Clone)]
+pub struct CompiledProgram<W, const N: usize> {
+    ip_register: Option<usize>,
+    program: Arc<Program>,
+    breakpoints: Vec<usize>,
+    /// Start addresses of the machine's superinstructions.
+    superinstructions: Vec<usize>,
+    ops: Vec<Result<Op<W>, Fault>>,
+    /// The value of the ip register while each instruction runs, `None` if it does not fit.
+    addresses: Vec<Option<W>>,
+    exits: Vec<Exit<W>>,
+    /// For each entry address, the address of the last instruction of the run starting there.
+    run_ends: Vec<usize>,
+}
+
+impl<W: Word, const N: usize> CompiledProgram<W, N> {
+    /// Compiles the program of `machine`. Runs never continue past a breakpoint, so the machine
+    /// can stop there, or into a superinstruction, so it can be applied.
+    pub fn new(machine: &Machine<W, N>) -> Self {
+        let ip_register = machine.ip_register;
+        let program = &machine.program;
+        let superinstructions = machine
+            .superinstructions
+            .iter()
+            .map(|s| s.address)
+            .collect::<Vec<_>>();
+        let stops = [&machine.breakpoints[..], &superinstructions].concat();
+
+        let len = program.len();
+        let ops = (0..len)
+            .map(|address| Op::new::<N>(ip_register, address, program))
+            .collect::<Vec<_>>();
+        let addresses = (0..len).map(W::from_usize).collect::<Vec<_>>();
+
+        let exits = (0..len)
+            .map(|address| Self::exit(ip_register, program, &ops, &stops, address))
+            .collect::<Vec<_>>();
+
+        let mut run_ends = vec![0; len];
+        for address in (0..len).rev() {
+            let ends_here = !matches!(exits[address], Exit::Next)
+                || address + 1 == len
+                || stops.contains(&(address + 1));
+
+            run_ends[address] = if ends_here {
+                address
+            } else {
+                run_ends[address + 1]
+            };
+        }
+
+        Self {
+            ip_register,
+            program: Arc::clone(program),
+            breakpoints: machine.breakpoints.clone(),
+            superinstructions,
+            ops,
+            addresses,
+            exits,
+            run_ends,
+        }
+    }
+
+    fn exit(
+        ip_register: Option<usize>,
+        program: &Program,
+        ops: &[Result<Op<W>, Fault>],
+        stops: &[usize],
+        address: usize,
+    ) -> Exit<W> {
+        let Some(r) = ip_register else {
+            return Exit::Next;
+        };
+
+        if program[address].output == r {
+            // Evaluating a constant instruction up front yields exactly the value, or the fault,
+            // the interpreter would see.
+            if let Ok(op) = &ops[address] {
+                let mut registers = [W::ZERO; N];
+                if op.is_constant() && op.apply(&mut registers).is_ok() {
+                    return Exit::Jump(next_ip(registers[r]));
+                }
+            }
+            return Exit::Computed(r);
+        }
+
+        let jump = address + 1;
+        if jump >= program.len() || stops.contains(&jump) {
+            return Exit::Next;
+        }
+
+        let is_branch =
+            matches!(flow(r, program, jump), Flow::Branch { compare, .. } if compare == address);
+        let ins = &program[jump];
+        let flag = program[address].output;
+        let reads_flag_and_ip =
+            (ins.input_a, ins.input_b) == (flag, r) || (ins.input_a, ins.input_b) == (r, flag);
+
+        match (W::from_usize(jump + 1), W::from_usize(jump)) {
+            (Some(taken), Some(fallthrough))
+                if is_branch && reads_flag_and_ip && ops[address].is_ok() && ops[jump].is_ok() =>
+            {
+                Exit::Branch {
+                    ip_register: r,
+                    flag,
+                    taken: (taken, jump + 2),
+                    fallthrough: (fallthrough, jump + 1),
+                }
+            }
+            _ => Exit::Next,
+        }
+    }
+
+    /// Executes the run starting at the machine's ip.
+    #[inline(always)]
+    fn run(&self, machine: &mut Machine<W, N>) -> Result<(), RunError> {
+        let entry = machine.ip;
+        let end = self.run_ends[entry];
+        let registers = &mut machine.registers;
+
+        for address in entry..=end {
+            let result = match &self.ops[address] {
+                Ok(op) => op.apply(registers),
+                Err(fault) => Err(*fault),
+            };
+
+            if let Err(fault) = result {
+                if let Some(r) = self.ip_register {
+                    // The interpreter writes the ip register before executing, unless the
+                    // address does not fit, in which case it still holds the previous address.
+                    let previous = address.checked_sub(1).filter(|&p| p >= entry);
+                    if let (Some(slot), Some(value)) = (
+                        registers.get_mut(r),
+                        self.addresses[address].or(previous.and_then(|p| self.addresses[p])),
+                    ) {
+                        *slot = value;
+                    }
+                }
+                machine.instructions_executed += address - entry;
+                machine.ip = address;
+                return Err(RunError::Fault { ip: address, fault });
+            }
+        }
+
+        machine.instructions_executed += end - entry + 1;
+        machine.ip = match self.exits[end] {
+            Exit::Next => {
+                if let (Some(r), Some(value)) = (self.ip_register, self.addresses[end]) {
+                    registers[r] = value;
+                }
+                end + 1
+            }
+            Exit::Jump(target) => target,
+            Exit::Branch {
+                ip_register,
+                flag,
+                taken,
+                fallthrough,
+            } => {
+                machine.instructions_executed += 1;
+                let (value, target) = if registers[flag] == W::ONE {
+                    taken
+                } else {
+                    fallthrough
+                };
+                registers[ip_register] = value;
+                target
+            }
+            Exit::Computed(r) => next_ip(registers[r]),
+        };
+
+        Ok(())
+    }
+
+    /// Whether this was compiled from the current program, ip register, breakpoints and
+    /// superinstructions of `machine`.
+    fn is_compiled_for(&self, machine: &Machine<W, N>) -> bool {
+        Arc::ptr_eq(&self.program, &machine.program)
+            && self.ip_register == machine.ip_register
+            && self.breakpoints == machine.breakpoints
+            && machine
+                .superinstructions
+                .iter()
+                .map(|s| s.address)
+                .eq(self.superinstructions.iter().copied())
+    }
+
+    /// Number of instructions executed by the run starting at `entry`.
+    fn run_length(&self, entry: usize) -> usize {
+        let end = self.run_ends[entry];
+        let extra = usize::from(matches!(self.exits[end], Exit::Branch { .. }));
+        end - entry + 1 + extra
+    }
+}
+
+/// The address following the one held in the ip register, past the end of any program if the
+/// value is not an address.
+fn next_ip<W: Word>(value: W) -> usize {
+    value
+        .to_usize()
+        .and_then(|ip| ip.checked_add(1))
+        .unwrap_or(usize::MAX)
+}
+
+impl<W: Word, const N: usize> Machine<W, N> {
+    /// Compiles the program with the current breakpoints and superinstructions. Compile again
+    /// after changing them.
+    pub fn compile(&self) -> CompiledProgram<W, N> {
+        CompiledProgram::new(self)
+    }
+
+    /// Like [`Self::run_until`] without a budget, but executing `compiled` a run of
+    /// instructions at a time, applying superinstructions where runs stop for them. Falls back
+    /// to the interpreter if watchpoints, cycle detection, tracing or profiling need to see every
+    /// instruction.
+    ///
+    /// Fails with [`RunError::NotCompiledFor`] if `compiled` was compiled from a different
+    /// program, ip register, breakpoints or superinstructions than the machine has now.
+    pub fn run_compiled(
+        &mut self,
+        compiled: &CompiledProgram<W, N>,
+    ) -> Result<StopReason, RunError> {
+        if !compiled.is_compiled_for(self) {
+            return Err(RunError::NotCompiledFor);
+        }
+
+        if self.is_instrumented() || self.detect_cycles || !self.watchpoints.is_empty() {
+            return self.run_until(None);
+        }
+
+        let start = Instant::now();
+        let mut check_time_at = self.instructions_executed;
+
+        loop {
+            if self.is_halted() {
+                return Ok(StopReason::Halted);
+            }
+
+            let executed = self.instructions_executed;
+
+            if let Some(limit) = self.time_limit {
+                if executed >= check_time_at {
+                    if start.elapsed() >= limit {
+                        return Err(RunError::TimeLimit(limit));
+                    }
+                    check_time_at = executed.saturating_add(crate::TIME_CHECK_INTERVAL);
+                }
+            }
+
+            let limit = self.instruction_limit;
+            if let Some(limit) = limit.filter(|&limit| executed >= limit) {
+                return Err(RunError::InstructionLimit(limit));
+            }
+
+            // Like the interpreter, a superinstruction may run past the limit.
+            if self.apply_superinstruction().is_none() {
+                match limit {
+                    // Single steps up to the limit, so it is hit exactly.
+                    Some(limit) if executed + compiled.run_length(self.ip) > limit => {
+                        self.step()?
+                    }
+                    _ => compiled.run(self)?,
+                }
+            }
+
+            if self.breakpoints.contains(&self.ip) {
+                return Ok(StopReason::Breakpoint(self.ip));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{read_file, read_file_part};
+    use crate::{parse_computer_program, Computer, Instruction};
+
+    fn load(day: u8, part: Option<u8>) -> Computer {
+        let day = crate::template::Day::new(day).unwrap();
+        let input = match part {
+            Some(part) => read_file_part("examples", day, part),
+            None => read_file("examples", day),
+        };
+        let (ip_register, program) = parse_computer_program(&input).unwrap();
+        Computer::new(ip_register, program)
+    }
+
+    /// Runs both machines to their next stop and checks they agree.
+    fn assert_same_stop<W: Word, const N: usize>(
+        interpreted: &mut Machine<W, N>,
+        compiled: &mut Machine<W, N>,
+    ) -> Result<StopReason, RunError> {
+        let code = compiled.compile();
+        let expected = interpreted.run_until(None);
+
+        assert_eq!(compiled.run_compiled(&code), expected);
+        assert_eq!(compiled.registers, interpreted.registers);
+        assert_eq!(compiled.ip, interpreted.ip);
+        assert_eq!(
+            compiled.instructions_executed,
+            interpreted.instructions_executed
+        );
+
+        expected
+    }
+
+    #[test]
+    fn matches_interpreter_on_day_19() {
+        for part in [None, Some(2)] {
+            let mut interpreted = load(19, part);
+            let mut compiled = load(19, part);
+
+            assert_eq!(
+                assert_same_stop(&mut interpreted, &mut compiled),
+                Ok(StopReason::Halted)
+            );
+        }
+    }
+
+    #[test]
+    fn applies_superinstructions_between_runs() {
+        for r0 in [0, 1] {
+            let mut interpreted = load(19, Some(2)).with_idioms();
+            interpreted.registers[0] = r0;
+            let mut compiled = load(19, Some(2)).with_idioms();
+            compiled.registers[0] = r0;
+
+            assert_eq!(
+                assert_same_stop(&mut interpreted, &mut compiled),
+                Ok(StopReason::Halted)
+            );
+        }
+    }
+
+    #[test]
+    fn matches_interpreter_at_day_21_breakpoints() {
+        let mut interpreted = load(21, Some(2));
+        let mut compiled = load(21, Some(2));
+        interpreted.add_breakpoint(23);
+        compiled.add_breakpoint(23);
+
+        for _ in 0..5 {
+            assert_eq!(
+                assert_same_stop(&mut interpreted, &mut compiled),
+                Ok(StopReason::Breakpoint(23))
+            );
+        }
+
+        interpreted.registers[0] = interpreted.registers[5];
+        compiled.registers[0] = compiled.registers[5];
+        assert_eq!(
+            assert_same_stop(&mut interpreted, &mut compiled),
+            Ok(StopReason::Halted)
+        );
+    }
+
+    #[test]
+    fn applies_superinstructions_up_to_day_21_breakpoints() {
+        let mut interpreted = load(21, Some(2)).with_idioms();
+        let mut compiled = load(21, Some(2)).with_idioms();
+        interpreted.add_breakpoint(23);
+        compiled.add_breakpoint(23);
+
+        for _ in 0..5 {
+            assert_eq!(
+                assert_same_stop(&mut interpreted, &mut compiled),
+                Ok(StopReason::Breakpoint(23))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_programs_compiled_for_another_machine() {
+        let mut computer = load(21, Some(2));
+        let code = computer.compile();
+
+        computer.add_breakpoint(23);
+        assert_eq!(computer.run_compiled(&code), Err(RunError::NotCompiledFor));
+
+        let mut other = load(21, Some(2));
+        other.add_breakpoint(23);
+        assert_eq!(
+            other.run_compiled(&computer.compile()),
+            Err(RunError::NotCompiledFor)
+        );
+
+        let mut idioms = load(19, Some(2)).with_idioms();
+        let code = load(19, Some(2)).compile();
+        assert_eq!(idioms.run_compiled(&code), Err(RunError::NotCompiledFor));
+    }
+
+    #[test]
+    fn stops_exactly_at_instruction_limit() {
+        let mut interpreted = load(19, Some(2)).with_instruction_limit(1001);
+        let mut compiled = load(19, Some(2)).with_instruction_limit(1001);
+
+        assert_eq!(
+            assert_same_stop(&mut interpreted, &mut compiled),
+            Err(RunError::InstructionLimit(1001))
+        );
+    }
+
+    #[test]
+    fn faults_like_the_interpreter() {
+        let program = "#ip 2\nseti 4000000000 0 0\nmulr 0 0 1\nseti 0 0 2";
+        let (ip_register, program) = parse_computer_program(program).unwrap();
+        let mut interpreted = Machine::<u32, 3>::new(ip_register, program.clone());
+        let mut compiled = Machine::<u32, 3>::new(ip_register, program);
+
+        assert_eq!(
+            assert_same_stop(&mut interpreted, &mut compiled),
+            Err(RunError::Fault {
+                ip: 1,
+                fault: Fault::Overflow
+            })
+        );
+    }
+
+    #[test]
+    fn faults_on_an_ip_register_past_the_last_register() {
+        let program = vec![Instruction::new(OpKind::Seti, 7, 0, 0)];
+        let mut interpreted = Machine::<usize, 4>::new(5, program.clone());
+        let mut compiled = Machine::<usize, 4>::new(5, program);
+
+        assert_eq!(
+            assert_same_stop(&mut interpreted, &mut compiled),
+            Err(RunError::Fault {
+                ip: 0,
+                fault: Fault::RegisterOutOfRange(5)
+            })
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Turns an ElfCode [`Program`] into annotated pseudo-code.
+
+use std::fmt;
+
+use crate::cfg::Cfg;
+use crate::{Instruction, OpKind, Operand, Program};
+
+/// How an instruction hands control to the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// The instruction does not write the ip register.
+    Next,
+    /// The instruction always continues at `target`. Targets past the end of the program halt.
+    Goto(usize),
+    /// The second half of a compare-then-jump pair: continues at `taken` if the compare at
+    /// `compare` held, `fallthrough` otherwise.
+    Branch {
+        compare: usize,
+        taken: usize,
+        fallthrough: usize,
+    },
+    /// The target depends on a register value only known at runtime.
+    Computed,
+}
+
+/// A value read by an instruction, with reads of the ip register folded to the current address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Const(usize),
+    Reg(usize),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Const(v) => write!(f, "{v}"),
+            Value::Reg(r) => write!(f, "r{r}"),
+        }
+    }
+}
+
+fn is_compare(op: OpKind) -> bool {
+    matches!(
+        op,
+        OpKind::Gtir | OpKind::Gtri | OpKind::Gtrr | OpKind::Eqir | OpKind::Eqri | OpKind::Eqrr
+    )
+}
+
+fn operator(op: OpKind) -> &'static str {
+    match op {
+        OpKind::Addr | OpKind::Addi => "+",
+        OpKind::Mulr | OpKind::Muli => "*",
+        OpKind::Banr | OpKind::Bani => "&",
+        OpKind::Borr | OpKind::Bori => "|",
+        OpKind::Gtir | OpKind::Gtri | OpKind::Gtrr => ">",
+        OpKind::Eqir | OpKind::Eqri | OpKind::Eqrr => "==",
+        OpKind::Setr | OpKind::Seti => "",
+    }
+}
+
+fn value(ip_register: usize, address: usize, operand: Operand, raw: usize) -> Option<Value> {
+    match operand {
+        Operand::Register if raw == ip_register => Some(Value::Const(address)),
+        Operand::Register => Some(Value::Reg(raw)),
+        Operand::Immediate => Some(Value::Const(raw)),
+        Operand::Ignored => None,
+    }
+}
+
+fn operands(ip_register: usize, address: usize, ins: &Instruction) -> (Value, Option<Value>) {
+    let a = value(ip_register, address, ins.op.operand_a(), ins.input_a).unwrap();
+    let b = value(ip_register, address, ins.op.operand_b(), ins.input_b);
+    (a, b)
+}
+
+/// Evaluates `ins` if every input it reads is known at `address`.
+fn constant_result(ip_register: usize, address: usize, ins: &Instruction) -> Option<usize> {
+    match operands(ip_register, address, ins) {
+        (Value::Const(_), None | Some(Value::Const(_))) => {
+            // Only the ip register can be read, so every other register shares the second slot.
+            let ins = ins.map_registers(|r| usize::from(r != ip_register));
+            let mut registers = [address, 0];
+            ins.execute(&mut registers).ok()?;
+            Some(registers[ins.output])
+        }
+        _ => None,
+    }
+}
+
+fn expression(ip_register: usize, address: usize, ins: &Instruction) -> String {
+    match operands(ip_register, address, ins) {
+        (a, None) => a.to_string(),
+        (a, Some(b)) => format!("{a} {} {b}", operator(ins.op)),
+    }
+}
+
+/// The compare at `address` as a condition, negated if `holds` is false.
+pub(crate) fn condition(
+    ip_register: usize,
+    address: usize,
+    ins: &Instruction,
+    holds: bool,
+) -> String {
+    if holds {
+        return expression(ip_register, address, ins);
+    }
+
+    let negated = match ins.op {
+        OpKind::Gtir | OpKind::Gtri | OpKind::Gtrr => "<=",
+        OpKind::Eqir | OpKind::Eqri | OpKind::Eqrr => "!=",
+        _ => return format!("!({})", expression(ip_register, address, ins)),
+    };
+
+    match operands(ip_register, address, ins) {
+        (a, Some(b)) => format!("{a} {negated} {b}"),
+        (a, None) => a.to_string(),
+    }
+}
+
+/// Classifies how control leaves the instruction at `address`.
+pub fn flow(ip_register: usize, program: &Program, address: usize) -> Flow {
+    let ins = &program[address];
+
+    if ins.output != ip_register {
+        return Flow::Next;
+    }
+
+    if let Some(value) = constant_result(ip_register, address, ins) {
+        // A jump to `usize::MAX` leaves the ip past the end of any program, so it halts too.
+        return Flow::Goto(value.saturating_add(1));
+    }
+
+    if ins.op == OpKind::Addr && address > 0 {
+        let flag = if ins.input_a == ip_register {
+            ins.input_b
+        } else {
+            ins.input_a
+        };
+        let previous = &program[address - 1];
+
+        if is_compare(previous.op) && previous.output == flag {
+            return Flow::Branch {
+                compare: address - 1,
+                taken: address + 2,
+                fallthrough: address + 1,
+            };
+        }
+    }
+
+    Flow::Computed
+}
+
+/// A single disassembled instruction.
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub address: usize,
+    pub instruction: Instruction,
+    pub pseudo: String,
+    /// Whether a basic block starts at this address.
+    pub is_leader: bool,
+    /// Addresses of the instructions that jump here.
+    pub jump_sources: Vec<usize>,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>5}  {:<20}; {}",
+            self.address,
+            self.instruction.to_string(),
+            self.pseudo
+        )
+    }
+}
+
+/// A whole program rendered as pseudo-code, split into labelled basic blocks. The listing is
+/// valid input for [`assemble`](crate::assembler::assemble).
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    pub ip_register: usize,
+    pub lines: Vec<Line>,
+}
+
+fn label(program: &Program, target: usize) -> String {
+    if target >= program.len() {
+        "halt".to_string()
+    } else {
+        format!("goto L{target}")
+    }
+}
+
+fn pseudo_code(ip_register: usize, program: &Program, address: usize) -> String {
+    let ins = &program[address];
+
+    match flow(ip_register, program, address) {
+        Flow::Next => format!(
+            "r{} = {}",
+            ins.output,
+            expression(ip_register, address, ins)
+        ),
+        Flow::Goto(target) => label(program, target),
+        Flow::Branch { compare, taken, .. } => format!(
+            "if {} {}",
+            expression(ip_register, compare, &program[compare]),
+            label(program, taken)
+        ),
+        Flow::Computed => format!("goto ({}) + 1", expression(ip_register, address, ins)),
+    }
+}
+
+impl Disassembly {
+    pub fn new(ip_register: usize, program: &Program) -> Self {
+        let cfg = Cfg::new(ip_register, program);
+        let mut sources = vec![Vec::new(); program.len()];
+
+        for edge in &cfg.edges {
+            let from = cfg.blocks[edge.from].last();
+            let to = cfg.blocks[edge.to].start;
+
+            if to != from + 1 {
+                sources[to].push(from);
+            }
+        }
+
+        let lines = program
+            .iter()
+            .zip(sources)
+            .enumerate()
+            .map(|(address, (&instruction, mut jump_sources))| {
+                jump_sources.sort_unstable();
+                Line {
+                    address,
+                    instruction,
+                    pseudo: pseudo_code(ip_register, program, address),
+                    is_leader: cfg.blocks.iter().any(|block| block.start == address),
+                    jump_sources,
+                }
+            })
+            .collect();
+
+        Self { ip_register, lines }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "#ip {}", self.ip_register)?;
+
+        for line in &self.lines {
+            if line.is_leader {
+                write!(f, "L{}:", line.address)?;
+                if !line.jump_sources.is_empty() {
+                    let sources = line
+                        .jump_sources
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>();
+                    write!(f, "  ; from {}", sources.join(", "))?;
+                }
+                writeln!(f)?;
+            }
+            writeln!(f, "{line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Shorthand for rendering a whole program.
+pub fn disassemble(ip_register: usize, program: &Program) -> String {
+    Disassembly::new(ip_register, program).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_computer_program;
+    use crate::template::read_file_part;
+
+    fn day_19() -> (usize, Program) {
+        parse_computer_program(&read_file_part("examples", crate::day!(19), 2)).unwrap()
+    }
+
+    #[test]
+    fn renders_jumps_and_branches() {
+        let (ip_register, program) = day_19();
+        let disassembly = Disassembly::new(ip_register, &program);
+        let pseudo = |address: usize| disassembly.lines[address].pseudo.as_str();
+
+        assert_eq!(pseudo(0), "goto L17");
+        assert_eq!(pseudo(3), "r4 = r2 * r3");
+        assert_eq!(pseudo(5), "if r4 == r5 goto L7");
+        assert_eq!(pseudo(16), "halt");
+        assert_eq!(pseudo(19), "goto (19 + r0) + 1");
+        assert_eq!(pseudo(21), "r4 = 21");
+    }
+
+    #[test]
+    fn halts_on_a_jump_to_the_last_address() {
+        let input = format!("#ip 0\nseti {} 0 0", usize::MAX);
+        let (ip_register, program) = parse_computer_program(&input).unwrap();
+
+        assert_eq!(flow(ip_register, &program, 0), Flow::Goto(usize::MAX));
+        assert_eq!(
+            Disassembly::new(ip_register, &program).lines[0].pseudo,
+            "halt"
+        );
+    }
+
+    #[test]
+    fn labels_basic_blocks() {
+        let (ip_register, program) = day_19();
+        let disassembly = Disassembly::new(ip_register, &program);
+
+        let leaders = disassembly
+            .lines
+            .iter()
+            .filter(|line| line.is_leader)
+            .map(|line| line.address)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            leaders,
+            vec![0, 1, 2, 3, 6, 7, 8, 11, 12, 15, 16, 17, 20, 21]
+        );
+        assert_eq!(disassembly.lines[3].jump_sources, vec![11]);
+
+        let text = disassembly.to_string();
+        assert!(text.starts_with("#ip 1\nL0:\n"));
+        assert!(text.contains("L3:  ; from 11\n    3  mulr 2 3 4          ; r4 = r2 * r3\n"));
+    }
+}
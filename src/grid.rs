@@ -0,0 +1,637 @@
+//! A 2D grid of cells addressed by [`IVec2`], stored densely over a rectangle or sparsely in a map.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use glam::IVec2;
+use itertools::Either;
+
+/// Offsets to the four orthogonal neighbours, in reading order.
+pub const NEIGHBORS_4: [IVec2; 4] = [
+    IVec2::new(0, -1),
+    IVec2::new(-1, 0),
+    IVec2::new(1, 0),
+    IVec2::new(0, 1),
+];
+
+/// Offsets to all eight neighbours, in reading order.
+pub const NEIGHBORS_8: [IVec2; 8] = [
+    IVec2::new(-1, -1),
+    IVec2::new(0, -1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 0),
+    IVec2::new(1, 0),
+    IVec2::new(-1, 1),
+    IVec2::new(0, 1),
+    IVec2::new(1, 1),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridErrorKind {
+    /// The character is not a known cell.
+    UnknownCell(char),
+    /// A row of a dense map differs in length from the first.
+    RaggedRow { expected: usize, found: usize },
+}
+
+/// An error produced while parsing a character map. `line` and `column` are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: GridErrorKind,
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+
+        match &self.kind {
+            GridErrorKind::UnknownCell(c) => write!(f, "unknown cell '{c}'"),
+            GridErrorKind::RaggedRow { expected, found } => {
+                write!(f, "expected a row of {expected} cells, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+/// A position ordered top to bottom, then left to right, for breaking ties the way the puzzles do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReadingOrder(pub IVec2);
+
+impl Ord for ReadingOrder {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.0.y, self.0.x).cmp(&(other.0.y, other.0.x))
+    }
+}
+
+impl PartialOrd for ReadingOrder {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<IVec2> for ReadingOrder {
+    fn from(pos: IVec2) -> Self {
+        Self(pos)
+    }
+}
+
+/// One of the four orthogonal directions, with y growing downwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Direction {
+    Up,
+    Left,
+    Right,
+    Down,
+}
+
+impl Direction {
+    /// Every direction, in the reading order of the neighbour each one leads to.
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Left,
+        Direction::Right,
+        Direction::Down,
+    ];
+
+    /// The direction drawn by an arrow such as `^` or `>`.
+    pub fn from_arrow(c: char) -> Option<Self> {
+        match c {
+            '^' => Some(Direction::Up),
+            '<' => Some(Direction::Left),
+            '>' => Some(Direction::Right),
+            'v' => Some(Direction::Down),
+            _ => None,
+        }
+    }
+
+    /// The arrow drawn for this direction, the inverse of [`Direction::from_arrow`].
+    pub fn arrow(self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Left => '<',
+            Direction::Right => '>',
+            Direction::Down => 'v',
+        }
+    }
+
+    pub const fn offset(self) -> IVec2 {
+        match self {
+            Direction::Up => IVec2::new(0, -1),
+            Direction::Left => IVec2::new(-1, 0),
+            Direction::Right => IVec2::new(1, 0),
+            Direction::Down => IVec2::new(0, 1),
+        }
+    }
+
+    /// The position one step from `pos` in this direction.
+    pub fn step(self, pos: IVec2) -> IVec2 {
+        pos + self.offset()
+    }
+
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Direction::Up | Direction::Down)
+    }
+
+    /// The direction a quarter turn anticlockwise, as seen on screen.
+    pub fn turn_left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    /// The direction a quarter turn clockwise, as seen on screen.
+    pub fn turn_right(self) -> Self {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    /// The orthogonal neighbours of `pos`, in reading order.
+    pub fn neighbors(pos: IVec2) -> impl Iterator<Item = IVec2> {
+        Self::ALL.into_iter().map(move |dir| dir.step(pos))
+    }
+}
+
+/// A rectangle of positions, including both corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: IVec2,
+    pub max: IVec2,
+}
+
+impl Bounds {
+    pub fn new(min: IVec2, max: IVec2) -> Self {
+        Self { min, max }
+    }
+
+    /// The smallest bounds containing every point, `None` if there are none.
+    pub fn of(points: impl IntoIterator<Item = IVec2>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(Self::new(first, first), |mut bounds, point| {
+            bounds.include(point);
+            bounds
+        }))
+    }
+
+    /// Grows the bounds to contain `pos`.
+    pub fn include(&mut self, pos: IVec2) {
+        self.min = self.min.min(pos);
+        self.max = self.max.max(pos);
+    }
+
+    /// The bounds grown by `by` on every side.
+    pub fn grow(self, by: i32) -> Self {
+        Self::new(self.min - by, self.max + by)
+    }
+
+    pub fn contains(&self, pos: IVec2) -> bool {
+        pos.cmpge(self.min).all() && pos.cmple(self.max).all()
+    }
+
+    /// Whether `pos` is in the outermost rows or columns.
+    pub fn on_edge(&self, pos: IVec2) -> bool {
+        self.contains(pos)
+            && (pos.x == self.min.x
+                || pos.x == self.max.x
+                || pos.y == self.min.y
+                || pos.y == self.max.y)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.max.x - self.min.x + 1
+    }
+
+    pub fn height(&self) -> i32 {
+        self.max.y - self.min.y + 1
+    }
+
+    /// Every position, in reading order.
+    pub fn positions(self) -> impl Iterator<Item = IVec2> {
+        (self.min.y..=self.max.y)
+            .flat_map(move |y| (self.min.x..=self.max.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    fn offset(&self, pos: IVec2) -> Option<usize> {
+        self.contains(pos).then(|| {
+            let relative = pos - self.min;
+            relative.y as usize * self.width() as usize + relative.x as usize
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Storage<T> {
+    /// One cell per position of the bounds, in reading order.
+    Dense(Vec<T>),
+    Sparse(HashMap<IVec2, T>),
+}
+
+/// Cells addressed by position. Dense grids have a cell at every position of their fixed
+/// bounds. Sparse grids only hold the cells that were inserted, and their bounds grow to
+/// contain them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    storage: Storage<T>,
+    bounds: Option<Bounds>,
+}
+
+impl<T> Grid<T> {
+    /// A dense grid over `bounds` with every cell set to `fill`.
+    pub fn dense(bounds: Bounds, fill: T) -> Self
+    where
+        T: Clone,
+    {
+        let cells = vec![fill; (bounds.width() * bounds.height()) as usize];
+
+        Self {
+            storage: Storage::Dense(cells),
+            bounds: Some(bounds),
+        }
+    }
+
+    /// A dense grid over `bounds` with each cell computed from its position.
+    pub fn from_fn(bounds: Bounds, cell: impl FnMut(IVec2) -> T) -> Self {
+        Self {
+            storage: Storage::Dense(bounds.positions().map(cell).collect()),
+            bounds: Some(bounds),
+        }
+    }
+
+    pub fn sparse() -> Self {
+        Self {
+            storage: Storage::Sparse(HashMap::new()),
+            bounds: None,
+        }
+    }
+
+    /// Parses a character map with one row per line into a dense grid, the first character at
+    /// `(0, 0)`. Every row must have the same length and `cell` must accept every character.
+    pub fn parse_dense(
+        input: &str,
+        mut cell: impl FnMut(char) -> Option<T>,
+    ) -> Result<Self, GridError> {
+        let mut cells = Vec::new();
+        let mut width = None;
+        let mut height = 0;
+
+        for (y, line) in input.lines().enumerate() {
+            let before = cells.len();
+            for (x, c) in line.chars().enumerate() {
+                cells.push(cell(c).ok_or(GridError {
+                    line: y + 1,
+                    column: x + 1,
+                    kind: GridErrorKind::UnknownCell(c),
+                })?);
+            }
+
+            let found = cells.len() - before;
+            if *width.get_or_insert(found) != found {
+                return Err(GridError {
+                    line: y + 1,
+                    column: 1,
+                    kind: GridErrorKind::RaggedRow {
+                        expected: width.unwrap_or_default(),
+                        found,
+                    },
+                });
+            }
+            height += 1;
+        }
+
+        let width = width.unwrap_or_default() as i32;
+        let bounds =
+            (width > 0).then(|| Bounds::new(IVec2::ZERO, IVec2::new(width - 1, height - 1)));
+
+        Ok(Self {
+            storage: Storage::Dense(cells),
+            bounds,
+        })
+    }
+
+    /// Parses a character map with one row per line into a sparse grid, the first character at
+    /// `(0, 0)`. `blank` characters leave their position empty and rows may differ in length.
+    pub fn parse_sparse(
+        input: &str,
+        blank: char,
+        mut cell: impl FnMut(char) -> Option<T>,
+    ) -> Result<Self, GridError> {
+        let mut grid = Self::sparse();
+
+        for (y, line) in input.lines().enumerate() {
+            for (x, c) in line.chars().enumerate().filter(|&(_, c)| c != blank) {
+                let value = cell(c).ok_or(GridError {
+                    line: y + 1,
+                    column: x + 1,
+                    kind: GridErrorKind::UnknownCell(c),
+                })?;
+                grid.insert(IVec2::new(x as i32, y as i32), value);
+            }
+        }
+
+        Ok(grid)
+    }
+
+    pub fn is_dense(&self) -> bool {
+        matches!(self.storage, Storage::Dense(_))
+    }
+
+    /// The positions spanned by the grid, `None` if it has no cells.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    /// The number of cells.
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Dense(cells) => cells.len(),
+            Storage::Sparse(cells) => cells.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether there is a cell at `pos`.
+    pub fn contains(&self, pos: IVec2) -> bool {
+        self.get(pos).is_some()
+    }
+
+    pub fn get(&self, pos: IVec2) -> Option<&T> {
+        match &self.storage {
+            Storage::Dense(cells) => cells.get(self.bounds?.offset(pos)?),
+            Storage::Sparse(cells) => cells.get(&pos),
+        }
+    }
+
+    pub fn get_mut(&mut self, pos: IVec2) -> Option<&mut T> {
+        match &mut self.storage {
+            Storage::Dense(cells) => cells.get_mut(self.bounds?.offset(pos)?),
+            Storage::Sparse(cells) => cells.get_mut(&pos),
+        }
+    }
+
+    /// Sets the cell at `pos`, returning the previous value.
+    ///
+    /// # Panics
+    ///
+    /// If the grid is dense and `pos` is outside of its bounds.
+    pub fn insert(&mut self, pos: IVec2, value: T) -> Option<T> {
+        match &mut self.storage {
+            Storage::Dense(_) => match self.get_mut(pos) {
+                Some(cell) => Some(std::mem::replace(cell, value)),
+                None => panic!("{pos} is outside of the dense grid"),
+            },
+            Storage::Sparse(cells) => {
+                match &mut self.bounds {
+                    Some(bounds) => bounds.include(pos),
+                    None => self.bounds = Some(Bounds::new(pos, pos)),
+                }
+                cells.insert(pos, value)
+            }
+        }
+    }
+
+    /// The cell at `pos`, inserting the default value first if it is empty.
+    ///
+    /// # Panics
+    ///
+    /// If the grid is dense and `pos` is outside of its bounds.
+    pub fn entry_or_default(&mut self, pos: IVec2) -> &mut T
+    where
+        T: Default,
+    {
+        if !self.contains(pos) {
+            self.insert(pos, T::default());
+        }
+        &mut self[pos]
+    }
+
+    /// Every cell with its position, in reading order.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, &T)> {
+        match &self.storage {
+            Storage::Dense(cells) => Either::Left(
+                self.bounds
+                    .into_iter()
+                    .flat_map(Bounds::positions)
+                    .zip(cells),
+            ),
+            Storage::Sparse(cells) => {
+                let mut sorted = cells
+                    .iter()
+                    .map(|(&pos, cell)| (pos, cell))
+                    .collect::<Vec<_>>();
+                sorted.sort_unstable_by_key(|&(pos, _)| (pos.y, pos.x));
+                Either::Right(sorted.into_iter())
+            }
+        }
+    }
+
+    /// Every cell, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        match &self.storage {
+            Storage::Dense(cells) => Either::Left(cells.iter()),
+            Storage::Sparse(cells) => Either::Right(cells.values()),
+        }
+    }
+
+    /// The orthogonal neighbours of `pos` that have a cell, in reading order.
+    pub fn neighbors4(&self, pos: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        Direction::neighbors(pos).filter(|&n| self.contains(n))
+    }
+
+    /// All neighbours of `pos`, including diagonals, that have a cell, in reading order.
+    pub fn neighbors8(&self, pos: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        NEIGHBORS_8
+            .into_iter()
+            .map(move |offset| pos + offset)
+            .filter(|&n| self.contains(n))
+    }
+
+    /// A grid of the same shape and storage with every cell passed through `f`.
+    pub fn map<U>(&self, mut f: impl FnMut(IVec2, &T) -> U) -> Grid<U> {
+        let storage = match &self.storage {
+            Storage::Dense(_) => {
+                Storage::Dense(self.iter().map(|(pos, cell)| f(pos, cell)).collect())
+            }
+            Storage::Sparse(cells) => Storage::Sparse(
+                cells
+                    .iter()
+                    .map(|(&pos, cell)| (pos, f(pos, cell)))
+                    .collect(),
+            ),
+        };
+
+        Grid {
+            storage,
+            bounds: self.bounds,
+        }
+    }
+
+    /// Draws `bounds` as text, one line per row. Positions without a cell are passed as `None`.
+    pub fn render_within(
+        &self,
+        bounds: Bounds,
+        mut cell: impl FnMut(IVec2, Option<&T>) -> char,
+    ) -> String {
+        let mut output = String::new();
+
+        for pos in bounds.positions() {
+            output.push(cell(pos, self.get(pos)));
+            if pos.x == bounds.max.x {
+                output.push('\n');
+            }
+        }
+
+        output
+    }
+
+    /// Draws the whole grid as text, one line per row.
+    pub fn render(&self, cell: impl FnMut(IVec2, Option<&T>) -> char) -> String {
+        self.bounds
+            .map(|bounds| self.render_within(bounds, cell))
+            .unwrap_or_default()
+    }
+}
+
+impl<T> Index<IVec2> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, pos: IVec2) -> &T {
+        self.get(pos).unwrap_or_else(|| panic!("no cell at {pos}"))
+    }
+}
+
+impl<T> IndexMut<IVec2> for Grid<T> {
+    fn index_mut(&mut self, pos: IVec2) -> &mut T {
+        self.get_mut(pos)
+            .unwrap_or_else(|| panic!("no cell at {pos}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    const MAP: &str = "#.#\n.##\n";
+
+    #[test]
+    fn parses_and_renders_dense_maps() {
+        let grid = Grid::parse_dense(MAP, |c| Some(c == '#')).unwrap();
+
+        assert!(grid.is_dense());
+        assert_eq!(
+            grid.bounds(),
+            Some(Bounds::new(IVec2::ZERO, IVec2::new(2, 1)))
+        );
+        assert!(grid[IVec2::new(1, 1)]);
+        assert_eq!(grid.get(IVec2::new(3, 0)), None);
+        assert_eq!(
+            grid.render(|_, cell| if cell == Some(&true) { '#' } else { '.' }),
+            MAP
+        );
+
+        assert_eq!(
+            Grid::parse_dense("..\n...\n", Some).unwrap_err(),
+            GridError {
+                line: 2,
+                column: 1,
+                kind: GridErrorKind::RaggedRow {
+                    expected: 2,
+                    found: 3
+                }
+            }
+        );
+        assert_eq!(
+            Grid::parse_dense("#x", |c| (c == '#').then_some(())).unwrap_err(),
+            GridError {
+                line: 1,
+                column: 2,
+                kind: GridErrorKind::UnknownCell('x')
+            }
+        );
+    }
+
+    #[test]
+    fn tracks_sparse_bounds() {
+        let mut grid = Grid::parse_sparse(" a\nb ", ' ', Some).unwrap();
+        assert!(!grid.is_dense());
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid.bounds(), Some(Bounds::new(IVec2::ZERO, IVec2::ONE)));
+
+        grid.insert(IVec2::new(-2, 3), 'c');
+        *grid.entry_or_default(IVec2::new(1, 0)) = 'd';
+        assert_eq!(
+            grid.bounds(),
+            Some(Bounds::new(IVec2::new(-2, 0), IVec2::new(1, 3)))
+        );
+        assert_eq!(grid.iter().map(|(_, &c)| c).collect::<String>(), "dbc");
+        assert_eq!(
+            grid.render(|_, cell| cell.copied().unwrap_or('.')),
+            "...d\n..b.\n....\nc...\n"
+        );
+    }
+
+    #[test]
+    fn lists_neighbors_in_reading_order() {
+        let grid = Grid::dense(Bounds::new(IVec2::ZERO, IVec2::splat(2)), ());
+
+        assert_eq!(
+            grid.neighbors4(IVec2::ONE).collect::<Vec<_>>(),
+            [
+                IVec2::new(1, 0),
+                IVec2::new(0, 1),
+                IVec2::new(2, 1),
+                IVec2::new(1, 2)
+            ]
+        );
+        assert_eq!(
+            grid.neighbors8(IVec2::ZERO).collect::<Vec<_>>(),
+            [IVec2::new(1, 0), IVec2::new(0, 1), IVec2::new(1, 1)]
+        );
+        assert_eq!(grid.neighbors8(IVec2::ONE).count(), 8);
+    }
+
+    #[test]
+    fn orders_positions_and_turns() {
+        let mut positions = [IVec2::new(2, 0), IVec2::new(0, 1), IVec2::new(1, 0)];
+        positions.sort_by_key(|&pos| ReadingOrder(pos));
+        assert_eq!(
+            positions,
+            [IVec2::new(1, 0), IVec2::new(2, 0), IVec2::new(0, 1)]
+        );
+
+        assert_eq!(Direction::ALL.map(Direction::offset), NEIGHBORS_4);
+        assert!(Direction::neighbors(IVec2::ONE)
+            .map(ReadingOrder)
+            .tuple_windows()
+            .all(|(a, b)| a < b));
+
+        for dir in Direction::ALL {
+            assert_eq!(dir.turn_left().turn_right(), dir);
+            assert_eq!(dir.turn_right().offset(), dir.offset().perp());
+            assert_ne!(dir.is_vertical(), dir.turn_left().is_vertical());
+        }
+        assert_eq!(Direction::from_arrow('v'), Some(Direction::Down));
+        assert_eq!(Direction::from_arrow('x'), None);
+        assert!(Direction::ALL
+            .into_iter()
+            .all(|dir| Direction::from_arrow(dir.arrow()) == Some(dir)));
+    }
+}
@@ -0,0 +1,572 @@
+//! Recognizes common ElfCode loops and replaces them with closed-form superinstructions.
+
+use crate::{Machine, OpKind, Program, Word};
+
+/// How an operand of a pattern instruction is matched.
+#[derive(Debug, Clone, Copy)]
+enum Arg {
+    /// A register playing the given role. Every role is bound to a distinct register other than
+    /// the ip register.
+    Reg(usize),
+    /// The ip register.
+    Ip,
+    /// A fixed immediate.
+    Lit(usize),
+    /// An immediate equal to the pattern's start address plus the offset.
+    Rel(usize),
+    /// Any immediate, captured into the given slot.
+    Cap(usize),
+    /// An operand the operation ignores.
+    Any,
+}
+
+type Shape = [(OpKind, Arg, Arg, Arg)];
+
+const MAX_ROLES: usize = 5;
+
+#[derive(Debug, Clone, Default)]
+struct Bindings {
+    registers: [Option<usize>; MAX_ROLES],
+    captures: [usize; 2],
+}
+
+impl Bindings {
+    fn bind(&mut self, ip_register: usize, start: usize, arg: Arg, value: usize) -> bool {
+        match arg {
+            Arg::Reg(role) => match self.registers[role] {
+                Some(register) => register == value,
+                None if value != ip_register && !self.registers.contains(&Some(value)) => {
+                    self.registers[role] = Some(value);
+                    true
+                }
+                None => false,
+            },
+            Arg::Ip => value == ip_register,
+            Arg::Lit(literal) => value == literal,
+            Arg::Rel(offset) => value == start + offset,
+            Arg::Cap(slot) => {
+                self.captures[slot] = value;
+                true
+            }
+            Arg::Any => true,
+        }
+    }
+
+    fn register(&self, role: usize) -> usize {
+        self.registers[role].expect("every role appears in its pattern")
+    }
+}
+
+/// Matches `shape` against the instructions starting at `start`. Inputs of commutative
+/// operations may appear in either order.
+fn match_shape(
+    ip_register: usize,
+    program: &Program,
+    start: usize,
+    shape: &Shape,
+) -> Option<Bindings> {
+    let instructions = program.get(start..start + shape.len())?;
+    let mut bindings = Bindings::default();
+
+    for (ins, &(op, a, b, c)) in instructions.iter().zip(shape) {
+        if ins.op != op {
+            return None;
+        }
+
+        let try_order = |x: usize, y: usize| {
+            let mut next = bindings.clone();
+            (next.bind(ip_register, start, a, x)
+                && next.bind(ip_register, start, b, y)
+                && next.bind(ip_register, start, c, ins.output))
+            .then_some(next)
+        };
+        let commutative = matches!(
+            op,
+            OpKind::Addr | OpKind::Mulr | OpKind::Banr | OpKind::Borr | OpKind::Eqrr
+        );
+
+        bindings = try_order(ins.input_a, ins.input_b).or_else(|| {
+            commutative
+                .then(|| try_order(ins.input_b, ins.input_a))
+                .flatten()
+        })?;
+    }
+
+    Some(bindings)
+}
+
+// Roles of the divisor sum pattern.
+const OUTER: usize = 0;
+const INNER: usize = 1;
+const FLAG: usize = 2;
+const TARGET: usize = 3;
+const SUM: usize = 4;
+
+/// Adds every `outer` for which some `inner` in `1..=target` gives `outer * inner == target`.
+const DIVISOR_SUM: [(OpKind, Arg, Arg, Arg); 15] = [
+    (OpKind::Seti, Arg::Lit(1), Arg::Any, Arg::Reg(OUTER)),
+    (OpKind::Seti, Arg::Lit(1), Arg::Any, Arg::Reg(INNER)),
+    (
+        OpKind::Mulr,
+        Arg::Reg(OUTER),
+        Arg::Reg(INNER),
+        Arg::Reg(FLAG),
+    ),
+    (
+        OpKind::Eqrr,
+        Arg::Reg(FLAG),
+        Arg::Reg(TARGET),
+        Arg::Reg(FLAG),
+    ),
+    (OpKind::Addr, Arg::Reg(FLAG), Arg::Ip, Arg::Ip),
+    (OpKind::Addi, Arg::Ip, Arg::Lit(1), Arg::Ip),
+    (OpKind::Addr, Arg::Reg(OUTER), Arg::Reg(SUM), Arg::Reg(SUM)),
+    (OpKind::Addi, Arg::Reg(INNER), Arg::Lit(1), Arg::Reg(INNER)),
+    (
+        OpKind::Gtrr,
+        Arg::Reg(INNER),
+        Arg::Reg(TARGET),
+        Arg::Reg(FLAG),
+    ),
+    (OpKind::Addr, Arg::Reg(FLAG), Arg::Ip, Arg::Ip),
+    (OpKind::Seti, Arg::Rel(1), Arg::Any, Arg::Ip),
+    (OpKind::Addi, Arg::Reg(OUTER), Arg::Lit(1), Arg::Reg(OUTER)),
+    (
+        OpKind::Gtrr,
+        Arg::Reg(OUTER),
+        Arg::Reg(TARGET),
+        Arg::Reg(FLAG),
+    ),
+    (OpKind::Addr, Arg::Reg(FLAG), Arg::Ip, Arg::Ip),
+    (OpKind::Seti, Arg::Rel(0), Arg::Any, Arg::Ip),
+];
+
+// Roles and captures of the division pattern.
+const QUOTIENT: usize = 0;
+const DIVIDEND: usize = 1;
+const DIVISOR: usize = 0;
+const EXIT: usize = 1;
+
+/// Counts `quotient` up from zero until `(quotient + 1) * divisor > dividend`.
+const DIVISION: [(OpKind, Arg, Arg, Arg); 9] = [
+    (OpKind::Seti, Arg::Lit(0), Arg::Any, Arg::Reg(QUOTIENT)),
+    (
+        OpKind::Addi,
+        Arg::Reg(QUOTIENT),
+        Arg::Lit(1),
+        Arg::Reg(FLAG),
+    ),
+    (
+        OpKind::Muli,
+        Arg::Reg(FLAG),
+        Arg::Cap(DIVISOR),
+        Arg::Reg(FLAG),
+    ),
+    (
+        OpKind::Gtrr,
+        Arg::Reg(FLAG),
+        Arg::Reg(DIVIDEND),
+        Arg::Reg(FLAG),
+    ),
+    (OpKind::Addr, Arg::Reg(FLAG), Arg::Ip, Arg::Ip),
+    (OpKind::Addi, Arg::Ip, Arg::Lit(1), Arg::Ip),
+    (OpKind::Seti, Arg::Cap(EXIT), Arg::Any, Arg::Ip),
+    (
+        OpKind::Addi,
+        Arg::Reg(QUOTIENT),
+        Arg::Lit(1),
+        Arg::Reg(QUOTIENT),
+    ),
+    (OpKind::Seti, Arg::Rel(0), Arg::Any, Arg::Ip),
+];
+
+// Roles of the multiplication pattern.
+const COUNTER: usize = 0;
+const ACCUMULATOR: usize = 1;
+const ADDEND: usize = 3;
+const COUNT: usize = 4;
+
+/// Adds `addend` to `accumulator` until `counter` reaches `count`.
+const MULTIPLY: [(OpKind, Arg, Arg, Arg); 6] = [
+    (OpKind::Seti, Arg::Lit(0), Arg::Any, Arg::Reg(COUNTER)),
+    (
+        OpKind::Addr,
+        Arg::Reg(ACCUMULATOR),
+        Arg::Reg(ADDEND),
+        Arg::Reg(ACCUMULATOR),
+    ),
+    (
+        OpKind::Addi,
+        Arg::Reg(COUNTER),
+        Arg::Lit(1),
+        Arg::Reg(COUNTER),
+    ),
+    (
+        OpKind::Eqrr,
+        Arg::Reg(COUNTER),
+        Arg::Reg(COUNT),
+        Arg::Reg(FLAG),
+    ),
+    (OpKind::Addr, Arg::Reg(FLAG), Arg::Ip, Arg::Ip),
+    (OpKind::Seti, Arg::Rel(0), Arg::Any, Arg::Ip),
+];
+
+/// A loop with a known closed form. Fields name the registers, or immediates, involved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idiom {
+    /// `sum += σ(target)`, testing every pair `outer * inner` in two nested loops.
+    DivisorSum {
+        outer: usize,
+        inner: usize,
+        flag: usize,
+        target: usize,
+        sum: usize,
+    },
+    /// `quotient = dividend / divisor`, counting up until the next multiple exceeds `dividend`.
+    /// Continues at `exit + 1`.
+    Division {
+        quotient: usize,
+        flag: usize,
+        dividend: usize,
+        divisor: usize,
+        exit: usize,
+    },
+    /// `accumulator += addend * count`, adding `addend` once per iteration.
+    Multiply {
+        counter: usize,
+        accumulator: usize,
+        addend: usize,
+        count: usize,
+        flag: usize,
+    },
+}
+
+/// A recognized loop covering the `len` instructions starting at `address`, entered at
+/// `address`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Superinstruction {
+    pub address: usize,
+    pub len: usize,
+    pub idiom: Idiom,
+}
+
+fn divisor_sum(n: usize) -> usize {
+    (1..)
+        .take_while(|d| d * d <= n)
+        .filter(|d| n.is_multiple_of(*d))
+        .map(|d| if d * d == n { d } else { d + n / d })
+        .sum()
+}
+
+impl Superinstruction {
+    /// Applies the effect of running the loop to completion, returning the next ip and the
+    /// number of instructions the loop would have executed.
+    ///
+    /// Returns `None`, leaving the registers untouched, if the closed form does not hold for the
+    /// current register values, e.g. because the loop would overflow or never finish. The loop
+    /// then has to be executed instruction by instruction.
+    pub fn apply<W: Word>(
+        &self,
+        ip_register: usize,
+        registers: &mut [W],
+    ) -> Option<(usize, usize)> {
+        let get = |r: usize| registers.get(r).copied();
+        let last = W::from_usize(self.address + self.len - 1)?;
+
+        let (writes, ip_value, next, executed) = match self.idiom {
+            Idiom::DivisorSum {
+                outer,
+                inner,
+                flag,
+                target,
+                sum,
+            } => {
+                let n = get(target)?;
+                // Products up to `n * n` are compared against `n`, so they must not overflow.
+                if n < W::ONE || n.mul(n)?.div(n)? != n {
+                    return None;
+                }
+
+                let steps = n.to_usize()?;
+                let total = get(sum)?.add(W::from_usize(divisor_sum(steps))?)?;
+                let past = n.add(W::ONE)?;
+                let executed = steps
+                    .checked_mul(steps)?
+                    .checked_mul(8)?
+                    .checked_add(steps * 4)?;
+
+                (
+                    vec![(outer, past), (inner, past), (flag, W::ONE), (sum, total)],
+                    last,
+                    self.address + self.len,
+                    executed,
+                )
+            }
+            Idiom::Division {
+                quotient,
+                flag,
+                dividend,
+                divisor,
+                exit,
+            } => {
+                let n = get(dividend)?;
+                let d = W::from_usize(divisor)?;
+                if n < W::ZERO || d <= W::ZERO {
+                    return None;
+                }
+
+                let q = n.div(d)?;
+                if q.add(W::ONE)?.mul(d)? <= n {
+                    return None;
+                }
+
+                let executed = q.to_usize()?.checked_mul(7)?.checked_add(6)?;
+                (
+                    vec![(quotient, q), (flag, W::ONE)],
+                    W::from_usize(exit)?,
+                    exit.checked_add(1)?,
+                    executed,
+                )
+            }
+            Idiom::Multiply {
+                counter,
+                accumulator,
+                addend,
+                count,
+                flag,
+            } => {
+                let n = get(count)?;
+                if n < W::ONE {
+                    return None;
+                }
+
+                let total = get(accumulator)?.add(get(addend)?.mul(n)?)?;
+                let executed = n.to_usize()?.checked_mul(5)?;
+
+                (
+                    vec![(counter, n), (accumulator, total), (flag, W::ONE)],
+                    last,
+                    self.address + self.len,
+                    executed,
+                )
+            }
+        };
+
+        if writes.iter().any(|&(r, _)| r >= registers.len()) || ip_register >= registers.len() {
+            return None;
+        }
+
+        registers[ip_register] = ip_value;
+        for (r, value) in writes {
+            registers[r] = value;
+        }
+
+        Some((next, executed))
+    }
+}
+
+/// Finds every recognized loop in `program`.
+pub fn recognize(ip_register: usize, program: &Program) -> Vec<Superinstruction> {
+    let mut found = Vec::new();
+
+    for address in 0..program.len() {
+        let matched = if let Some(b) = match_shape(ip_register, program, address, &DIVISOR_SUM) {
+            Some((
+                DIVISOR_SUM.len(),
+                Idiom::DivisorSum {
+                    outer: b.register(OUTER),
+                    inner: b.register(INNER),
+                    flag: b.register(FLAG),
+                    target: b.register(TARGET),
+                    sum: b.register(SUM),
+                },
+            ))
+        } else if let Some(b) = match_shape(ip_register, program, address, &DIVISION) {
+            Some((
+                DIVISION.len(),
+                Idiom::Division {
+                    quotient: b.register(QUOTIENT),
+                    flag: b.register(FLAG),
+                    dividend: b.register(DIVIDEND),
+                    divisor: b.captures[DIVISOR],
+                    exit: b.captures[EXIT],
+                },
+            ))
+        } else {
+            match_shape(ip_register, program, address, &MULTIPLY).map(|b| {
+                (
+                    MULTIPLY.len(),
+                    Idiom::Multiply {
+                        counter: b.register(COUNTER),
+                        accumulator: b.register(ACCUMULATOR),
+                        addend: b.register(ADDEND),
+                        count: b.register(COUNT),
+                        flag: b.register(FLAG),
+                    },
+                )
+            })
+        };
+
+        if let Some((len, idiom)) = matched {
+            found.push(Superinstruction {
+                address,
+                len,
+                idiom,
+            });
+        }
+    }
+
+    found
+}
+
+impl<W: Word, const N: usize> Machine<W, N> {
+    /// Replaces recognized loops with superinstructions. Requires a bound ip register.
+    pub fn with_idioms(mut self) -> Self {
+        if let Some(ip_register) = self.ip_register {
+            self.superinstructions = recognize(ip_register, &self.program);
+        }
+        self
+    }
+
+    /// Runs the superinstruction starting at `ip`, if there is one and it applies, returning the
+    /// number of instructions it stood in for.
+    pub(crate) fn apply_superinstruction(&mut self) -> Option<usize> {
+        let ip_register = self.ip_register?;
+        let superinstruction = self
+            .superinstructions
+            .iter()
+            .find(|s| s.address == self.ip)?;
+
+        let (ip, executed) = superinstruction.apply(ip_register, &mut self.registers)?;
+        self.ip = ip;
+        self.instructions_executed += executed;
+        Some(executed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{read_file_part, Day};
+    use crate::{parse_computer_program, Computer, StopReason};
+
+    fn load(day: u8) -> Computer {
+        let input = read_file_part("examples", Day::new(day).unwrap(), 2);
+        let (ip_register, program) = parse_computer_program(&input).unwrap();
+        Computer::new(ip_register, program)
+    }
+
+    /// Runs both machines to their next stop and checks they end in the same state.
+    fn assert_same_stop(plain: &mut Computer, fast: &mut Computer) {
+        assert_eq!(fast.run_until(None), plain.run_until(None));
+        assert_eq!(fast.registers, plain.registers);
+        assert_eq!(fast.ip, plain.ip);
+        assert_eq!(fast.instructions_executed, plain.instructions_executed);
+    }
+
+    #[test]
+    fn recognizes_divisor_sum() {
+        let computer = load(19);
+        let found = recognize(computer.ip_register.unwrap(), &computer.program);
+
+        assert_eq!(
+            found,
+            vec![Superinstruction {
+                address: 1,
+                len: 15,
+                idiom: Idiom::DivisorSum {
+                    outer: 2,
+                    inner: 3,
+                    flag: 4,
+                    target: 5,
+                    sum: 0
+                }
+            }]
+        );
+
+        let mut fast = load(19).with_idioms();
+        assert_same_stop(&mut load(19), &mut fast);
+        assert_eq!(fast.registers[0], 91);
+    }
+
+    #[test]
+    fn watchpoints_see_superinstruction_writes() {
+        let mut fast = load(19).with_idioms();
+        fast.add_watchpoint(0);
+
+        assert_eq!(
+            fast.run_until(None),
+            Ok(StopReason::Watchpoint {
+                register: 0,
+                address: 1
+            })
+        );
+        assert_eq!(fast.registers[0], 91);
+
+        fast.remove_watchpoint(0);
+        assert_eq!(fast.run_until(None), Ok(StopReason::Halted));
+    }
+
+    #[test]
+    fn recognizes_division() {
+        let mut computer = load(21);
+        let found = recognize(computer.ip_register.unwrap(), &computer.program);
+
+        assert_eq!(
+            found,
+            vec![Superinstruction {
+                address: 12,
+                len: 9,
+                idiom: Idiom::Division {
+                    quotient: 3,
+                    flag: 1,
+                    dividend: 4,
+                    divisor: 16,
+                    exit: 20
+                }
+            }]
+        );
+
+        let mut fast = load(21).with_idioms();
+        computer.add_breakpoint(23);
+        fast.add_breakpoint(23);
+        for _ in 0..3 {
+            assert_same_stop(&mut computer, &mut fast);
+        }
+    }
+
+    #[test]
+    fn recognizes_counted_multiply() {
+        let program = "#ip 5\nseti 7 0 1\nseti 6 0 3\nseti 0 0 2\naddr 1 0 0\naddi 2 1 2\neqrr 3 2 4\naddr 4 5 5\nseti 2 0 5";
+        let (ip_register, program) = parse_computer_program(program).unwrap();
+        let found = recognize(ip_register, &program);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].idiom,
+            Idiom::Multiply {
+                counter: 2,
+                accumulator: 0,
+                addend: 1,
+                count: 3,
+                flag: 4
+            }
+        );
+
+        let mut fast = Computer::new(ip_register, program.clone()).with_idioms();
+        assert_same_stop(&mut Computer::new(ip_register, program), &mut fast);
+        assert_eq!(fast.registers[0], 42);
+    }
+
+    #[test]
+    fn falls_back_when_the_closed_form_does_not_apply() {
+        let program =
+            "#ip 5\nseti 0 0 2\naddr 1 0 0\naddi 2 1 2\neqrr 3 2 4\naddr 4 5 5\nseti 0 0 5";
+        let (ip_register, program) = parse_computer_program(program).unwrap();
+        let mut computer = Computer::new(ip_register, program).with_idioms();
+        computer.registers[3] = 0;
+
+        assert_eq!(computer.apply_superinstruction(), None);
+        assert_eq!(computer.ip, 0);
+    }
+}
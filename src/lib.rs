@@ -1,207 +1,1238 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use idioms::Superinstruction;
+use trace::{Profile, Tracer};
+
+pub use compiled::CompiledProgram;
+pub use grid::Grid;
+pub use word::{Fault, Word};
+
+pub mod analysis;
+pub mod assembler;
+pub mod cfg;
+pub mod compiled;
+pub mod disassembler;
+pub mod grid;
+pub mod idioms;
+pub mod opcodes;
+pub mod render;
 pub mod template;
+pub mod trace;
+mod word;
 
 // Use this file to add helper functions and additional modules.
 
-pub type Opcode = fn(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize);
-
-fn addr(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    let b = registers[input_b];
-    registers[output_c] = a + b;
-}
-fn addi(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    registers[output_c] = a + input_b;
+/// The sixteen operations understood by the wrist device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum OpKind {
+    Addr,
+    Addi,
+    Mulr,
+    Muli,
+    Banr,
+    Bani,
+    Borr,
+    Bori,
+    Setr,
+    Seti,
+    Gtir,
+    Gtri,
+    Gtrr,
+    Eqir,
+    Eqri,
+    Eqrr,
 }
 
-fn mulr(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    let b = registers[input_b];
-    registers[output_c] = a * b;
-}
-fn muli(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    registers[output_c] = a * input_b;
-}
+impl OpKind {
+    pub const ALL: [OpKind; 16] = [
+        OpKind::Addr,
+        OpKind::Addi,
+        OpKind::Mulr,
+        OpKind::Muli,
+        OpKind::Banr,
+        OpKind::Bani,
+        OpKind::Borr,
+        OpKind::Bori,
+        OpKind::Setr,
+        OpKind::Seti,
+        OpKind::Gtir,
+        OpKind::Gtri,
+        OpKind::Gtrr,
+        OpKind::Eqir,
+        OpKind::Eqri,
+        OpKind::Eqrr,
+    ];
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            OpKind::Addr => "addr",
+            OpKind::Addi => "addi",
+            OpKind::Mulr => "mulr",
+            OpKind::Muli => "muli",
+            OpKind::Banr => "banr",
+            OpKind::Bani => "bani",
+            OpKind::Borr => "borr",
+            OpKind::Bori => "bori",
+            OpKind::Setr => "setr",
+            OpKind::Seti => "seti",
+            OpKind::Gtir => "gtir",
+            OpKind::Gtri => "gtri",
+            OpKind::Gtrr => "gtrr",
+            OpKind::Eqir => "eqir",
+            OpKind::Eqri => "eqri",
+            OpKind::Eqrr => "eqrr",
+        }
+    }
 
-fn banr(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    let b = registers[input_b];
-    registers[output_c] = a & b;
+    /// Applies the operation to `registers`, writing the result to `output_c`. The registers are
+    /// left untouched if the operation faults.
+    #[inline(always)]
+    pub fn execute<W: Word>(
+        self,
+        registers: &mut [W],
+        input_a: usize,
+        input_b: usize,
+        output_c: usize,
+    ) -> Result<(), Fault> {
+        let reg = |r: usize| {
+            registers
+                .get(r)
+                .copied()
+                .ok_or(Fault::RegisterOutOfRange(r))
+        };
+        let imm = |v: usize| W::from_usize(v).ok_or(Fault::Overflow);
+        let flag = |b: bool| if b { W::ONE } else { W::ZERO };
+
+        let value = match self {
+            OpKind::Addr => reg(input_a)?.add(reg(input_b)?).ok_or(Fault::Overflow)?,
+            OpKind::Addi => reg(input_a)?.add(imm(input_b)?).ok_or(Fault::Overflow)?,
+            OpKind::Mulr => reg(input_a)?.mul(reg(input_b)?).ok_or(Fault::Overflow)?,
+            OpKind::Muli => reg(input_a)?.mul(imm(input_b)?).ok_or(Fault::Overflow)?,
+            OpKind::Banr => reg(input_a)?.bitand(reg(input_b)?),
+            OpKind::Bani => reg(input_a)?.bitand(imm(input_b)?),
+            OpKind::Borr => reg(input_a)?.bitor(reg(input_b)?),
+            OpKind::Bori => reg(input_a)?.bitor(imm(input_b)?),
+            OpKind::Setr => reg(input_a)?,
+            OpKind::Seti => imm(input_a)?,
+            OpKind::Gtir => flag(imm(input_a)? > reg(input_b)?),
+            OpKind::Gtri => flag(reg(input_a)? > imm(input_b)?),
+            OpKind::Gtrr => flag(reg(input_a)? > reg(input_b)?),
+            OpKind::Eqir => flag(imm(input_a)? == reg(input_b)?),
+            OpKind::Eqri => flag(reg(input_a)? == imm(input_b)?),
+            OpKind::Eqrr => flag(reg(input_a)? == reg(input_b)?),
+        };
+
+        *registers
+            .get_mut(output_c)
+            .ok_or(Fault::RegisterOutOfRange(output_c))? = value;
+
+        Ok(())
+    }
 }
-fn bani(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    registers[output_c] = a & input_b;
+
+impl fmt::Display for OpKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
 }
 
-fn borr(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    let b = registers[input_b];
-    registers[output_c] = a | b;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownOpcode(pub String);
+
+impl fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode \"{}\"", self.0)
+    }
 }
-fn bori(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    registers[output_c] = a | input_b;
+
+impl FromStr for OpKind {
+    type Err = UnknownOpcode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OpKind::ALL
+            .into_iter()
+            .find(|op| op.mnemonic() == s)
+            .ok_or_else(|| UnknownOpcode(s.to_owned()))
+    }
 }
 
-fn setr(registers: &mut [usize], input_a: usize, _: usize, output_c: usize) {
-    registers[output_c] = registers[input_a];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub op: OpKind,
+    pub input_a: usize,
+    pub input_b: usize,
+    pub output: usize,
 }
-fn seti(registers: &mut [usize], input_a: usize, _: usize, output_c: usize) {
-    registers[output_c] = input_a;
+
+impl Instruction {
+    pub fn new(op: OpKind, input_a: usize, input_b: usize, output: usize) -> Self {
+        Self {
+            op,
+            input_a,
+            input_b,
+            output,
+        }
+    }
+
+    #[inline(always)]
+    pub fn execute<W: Word>(&self, registers: &mut [W]) -> Result<(), Fault> {
+        self.op
+            .execute(registers, self.input_a, self.input_b, self.output)
+    }
+
+    /// The same instruction with every register operand passed through `rename`.
+    pub fn map_registers(&self, rename: impl Fn(usize) -> usize) -> Self {
+        let map = |operand, raw| match operand {
+            Operand::Register => rename(raw),
+            Operand::Immediate | Operand::Ignored => raw,
+        };
+
+        Self {
+            op: self.op,
+            input_a: map(self.op.operand_a(), self.input_a),
+            input_b: map(self.op.operand_b(), self.input_b),
+            output: rename(self.output),
+        }
+    }
 }
 
-fn gtir(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let b = registers[input_b];
-    registers[output_c] = if input_a > b { 1 } else { 0 };
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.op, self.input_a, self.input_b, self.output
+        )
+    }
 }
-fn gtri(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    registers[output_c] = if a > input_b { 1 } else { 0 };
+
+pub type Program = Vec<Instruction>;
+
+/// Why [`Computer::run_until`] returned control to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The ip moved outside of the program.
+    Halted,
+    /// The ip reached a breakpoint. The instruction at `address` has not been executed yet.
+    Breakpoint(usize),
+    /// The instruction or superinstruction at `address` wrote to a watched register.
+    Watchpoint { register: usize, address: usize },
+    /// The instruction budget passed to `run_until` ran out.
+    BudgetExhausted,
 }
-fn gtrr(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    let b = registers[input_b];
-    registers[output_c] = if a > b { 1 } else { 0 };
+
+/// Why a run was aborted before the program halted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunError {
+    /// `instruction_limit` instructions were executed in total.
+    InstructionLimit(usize),
+    /// The run took longer than `time_limit`.
+    TimeLimit(Duration),
+    /// The machine returned to a state it was in before, so it will never halt. The registers
+    /// are left in that state.
+    Cycle { ip: usize, period: usize },
+    /// The instruction at `ip` could not be executed.
+    Fault { ip: usize, fault: Fault },
+    /// A [`CompiledProgram`] was run on a machine whose program, ip register, breakpoints or
+    /// superinstructions differ from those it was compiled from.
+    NotCompiledFor,
 }
 
-fn eqir(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let b = registers[input_b];
-    registers[output_c] = if input_a == b { 1 } else { 0 };
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::InstructionLimit(limit) => {
+                write!(f, "instruction limit of {limit} reached")
+            }
+            RunError::TimeLimit(limit) => write!(f, "time limit of {limit:?} reached"),
+            RunError::Cycle { ip, period } => {
+                write!(f, "state at ip {ip} repeats every {period} instructions")
+            }
+            RunError::Fault { ip, fault } => write!(f, "{fault} at ip {ip}"),
+            RunError::NotCompiledFor => {
+                write!(f, "the compiled program does not match the machine")
+            }
+        }
+    }
 }
-fn eqri(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    registers[output_c] = if a == input_b { 1 } else { 0 };
+
+impl std::error::Error for RunError {}
+
+/// Anything that can go wrong while loading or running an ElfCode program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComputerError {
+    Parse(ParseError),
+    Run(RunError),
 }
-fn eqrr(registers: &mut [usize], input_a: usize, input_b: usize, output_c: usize) {
-    let a = registers[input_a];
-    let b = registers[input_b];
-    registers[output_c] = if a == b { 1 } else { 0 };
+
+impl fmt::Display for ComputerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ComputerError::Parse(e) => write!(f, "parse error at {e}"),
+            ComputerError::Run(e) => write!(f, "run aborted: {e}"),
+        }
+    }
 }
 
-pub fn load_opcodes() -> HashMap<String, Opcode> {
-    let mut opcodes: HashMap<String, Opcode> = HashMap::new();
+impl std::error::Error for ComputerError {}
 
-    opcodes.insert("addr".to_string(), addr);
-    opcodes.insert("addi".to_string(), addi);
+impl From<ParseError> for ComputerError {
+    fn from(e: ParseError) -> Self {
+        ComputerError::Parse(e)
+    }
+}
 
-    opcodes.insert("mulr".to_string(), mulr);
-    opcodes.insert("muli".to_string(), muli);
+impl From<RunError> for ComputerError {
+    fn from(e: RunError) -> Self {
+        ComputerError::Run(e)
+    }
+}
 
-    opcodes.insert("banr".to_string(), banr);
-    opcodes.insert("bani".to_string(), bani);
+/// Brent's cycle detection over `(ip, registers)`: compares the current state against a saved
+/// one, moving the saved state forward at every power of two.
+struct CycleDetector<S> {
+    saved: S,
+    power: usize,
+    steps: usize,
+}
 
-    opcodes.insert("borr".to_string(), borr);
-    opcodes.insert("bori".to_string(), bori);
+impl<S: PartialEq> CycleDetector<S> {
+    fn new(state: S) -> Self {
+        Self {
+            saved: state,
+            power: 1,
+            steps: 0,
+        }
+    }
 
-    opcodes.insert("setr".to_string(), setr);
-    opcodes.insert("seti".to_string(), seti);
+    /// Returns the cycle length if `state` was seen before.
+    fn check(&mut self, state: S) -> Option<usize> {
+        self.steps += 1;
 
-    opcodes.insert("gtir".to_string(), gtir);
-    opcodes.insert("gtri".to_string(), gtri);
-    opcodes.insert("gtrr".to_string(), gtrr);
+        if state == self.saved {
+            return Some(self.steps);
+        }
 
-    opcodes.insert("eqir".to_string(), eqir);
-    opcodes.insert("eqri".to_string(), eqri);
-    opcodes.insert("eqrr".to_string(), eqrr);
+        if self.steps == self.power {
+            self.saved = state;
+            self.power *= 2;
+            self.steps = 0;
+        }
 
-    opcodes
+        None
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct Instruction {
-    label: String,
-    input_a: usize,
-    input_b: usize,
-    output: usize,
-}
+/// How often the wall clock is checked against `time_limit`, in instructions.
+const TIME_CHECK_INTERVAL: usize = 1 << 16;
 
-impl Instruction {
-    pub fn new(label: &str, input_a: usize, input_b: usize, output: usize) -> Self {
-        Self {
-            label: label.to_owned(),
-            input_a,
-            input_b,
-            output,
-        }
-    }
+/// Number of registers on the wrist device.
+pub const REGISTER_COUNT: usize = 6;
+
+/// An ElfCode machine with `N` registers of type `W`.
+///
+/// If `ip_register` is set, the instruction pointer is written to that register before each
+/// instruction and read back afterwards. Otherwise instructions simply run in order.
+pub struct Machine<W: Word, const N: usize> {
+    pub ip_register: Option<usize>,
+    pub ip: usize,
+    pub registers: [W; N],
+    pub instructions_executed: usize,
+    /// Shared between forks of the machine.
+    pub program: Arc<Program>,
+    pub breakpoints: Vec<usize>,
+    pub watchpoints: Vec<usize>,
+    /// Abort once `instructions_executed` reaches this value.
+    pub instruction_limit: Option<usize>,
+    /// Abort a single call to `run`/`run_until` once it has taken this long.
+    pub time_limit: Option<Duration>,
+    /// Abort when the machine revisits a state, since it would then loop forever.
+    pub detect_cycles: bool,
+    pub tracer: Option<Tracer>,
+    pub profile: Option<Profile>,
+    /// Loops replaced by their closed form, see [`Self::with_idioms`]. Breakpoints inside them
+    /// are not hit, watchpoints stop after the whole loop.
+    pub superinstructions: Vec<Superinstruction>,
 }
 
-pub type Program = Vec<Instruction>;
+/// The six register, `usize` machine used by the puzzles.
+pub type Computer = Machine<usize, REGISTER_COUNT>;
 
-pub struct Computer {
-    pub opcodes: HashMap<String, Opcode>,
-    pub ip_register: usize,
+/// The part of a machine's state that changes while it runs, see [`Machine::snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct State<W, const N: usize> {
     pub ip: usize,
-    pub registers: [usize; 6],
+    pub registers: [W; N],
     pub instructions_executed: usize,
-    pub program: Program,
 }
 
-impl Computer {
-    pub fn new(ip_register: usize, program: Program) -> Self {
+impl<W: Word, const N: usize> Machine<W, N> {
+    pub fn new(ip_register: usize, program: impl Into<Arc<Program>>) -> Self {
+        Self::with_ip_register(Some(ip_register), program)
+    }
+
+    /// A machine whose instruction pointer is not bound to any register.
+    pub fn unbound(program: impl Into<Arc<Program>>) -> Self {
+        Self::with_ip_register(None, program)
+    }
+
+    fn with_ip_register(ip_register: Option<usize>, program: impl Into<Arc<Program>>) -> Self {
         Self {
-            opcodes: load_opcodes(),
             ip_register,
             ip: 0,
-            registers: [0, 0, 0, 0, 0, 0],
+            registers: [W::ZERO; N],
             instructions_executed: 0,
-            program,
+            program: program.into(),
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            instruction_limit: None,
+            time_limit: None,
+            detect_cycles: false,
+            tracer: None,
+            profile: None,
+            superinstructions: Vec::new(),
         }
     }
 
+    pub fn with_instruction_limit(mut self, limit: usize) -> Self {
+        self.instruction_limit = Some(limit);
+        self
+    }
+
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    pub fn with_cycle_detection(mut self) -> Self {
+        self.detect_cycles = true;
+        self
+    }
+
+    pub fn with_tracer(mut self, tracer: Tracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Counts the instructions executed at each address. [`Self::profile_report`] is printed to
+    /// stderr when [`Self::run`] returns.
+    pub fn with_profile(mut self) -> Self {
+        self.profile = Some(Profile::new(self.program.len()));
+        self
+    }
+
+    /// Summary of the hottest instructions and loops, if profiling is enabled and the
+    /// instruction pointer is bound to a register.
+    pub fn profile_report(&self) -> Option<String> {
+        let profile = self.profile.as_ref()?;
+        Some(profile.report(self.ip_register?, &self.program))
+    }
+
     pub fn reset_program(&mut self) {
         self.ip = 0;
-        self.registers = [0, 0, 0, 0, 0, 0];
+        self.registers = [W::ZERO; N];
         self.instructions_executed = 0;
     }
 
-    pub fn tick(&mut self) {
-        let instruction = &self.program[self.ip];
+    pub fn snapshot(&self) -> State<W, N> {
+        State {
+            ip: self.ip,
+            registers: self.registers,
+            instructions_executed: self.instructions_executed,
+        }
+    }
 
-        self.registers[self.ip_register] = self.ip;
+    pub fn restore(&mut self, state: State<W, N>) {
+        self.ip = state.ip;
+        self.registers = state.registers;
+        self.instructions_executed = state.instructions_executed;
+    }
 
-        let opcode = self.opcodes.get(&instruction.label).unwrap();
+    /// An independent copy of the machine that shares its program. Everything but the tracer is
+    /// copied, so the fork can explore a different path from the same point.
+    pub fn fork(&self) -> Self {
+        Self {
+            ip_register: self.ip_register,
+            ip: self.ip,
+            registers: self.registers,
+            instructions_executed: self.instructions_executed,
+            program: Arc::clone(&self.program),
+            breakpoints: self.breakpoints.clone(),
+            watchpoints: self.watchpoints.clone(),
+            instruction_limit: self.instruction_limit,
+            time_limit: self.time_limit,
+            detect_cycles: self.detect_cycles,
+            tracer: None,
+            profile: self.profile.clone(),
+            superinstructions: self.superinstructions.clone(),
+        }
+    }
 
-        opcode(
-            &mut self.registers,
-            instruction.input_a,
-            instruction.input_b,
-            instruction.output,
-        );
+    pub fn add_breakpoint(&mut self, address: usize) {
+        if !self.breakpoints.contains(&address) {
+            self.breakpoints.push(address);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) {
+        self.breakpoints.retain(|&b| b != address);
+    }
+
+    pub fn add_watchpoint(&mut self, register: usize) {
+        if !self.watchpoints.contains(&register) {
+            self.watchpoints.push(register);
+        }
+    }
+
+    pub fn remove_watchpoint(&mut self, register: usize) {
+        self.watchpoints.retain(|&w| w != register);
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.ip >= self.program.len()
+    }
+
+    fn is_instrumented(&self) -> bool {
+        self.tracer.is_some() || self.profile.is_some()
+    }
+
+    /// Executes the instruction at `ip`, or the superinstruction starting there. On a fault, `ip`
+    /// and the output register do not change.
+    pub fn tick(&mut self) -> Result<(), RunError> {
+        if !self.is_instrumented() {
+            return match self.apply_superinstruction() {
+                Some(_) => Ok(()),
+                None => self.step(),
+            };
+        }
+
+        let ip = self.ip;
+        let instruction = self.program[ip];
+
+        let mut before = self.registers;
+        if let Some(r) = self.ip_register.filter(|&r| r < N) {
+            before[r] = W::from_usize(ip).unwrap_or(before[r]);
+        }
+
+        let replaced = self.apply_superinstruction();
+        if replaced.is_none() {
+            self.step()?;
+        }
+
+        if let Some(profile) = self.profile.as_mut() {
+            match replaced {
+                Some(executed) => profile.record_superinstruction(ip, executed),
+                None => profile.record(ip),
+            }
+        }
+
+        if let Some(tracer) = self.tracer.as_mut() {
+            match replaced {
+                Some(executed) => {
+                    tracer.record_superinstruction(ip, executed, &before, &self.registers);
+                }
+                None => tracer.record(ip, &instruction, &before, &self.registers),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// [`Self::tick`] without tracing and profiling, which would slow down the hot loop.
+    #[inline(always)]
+    fn step(&mut self) -> Result<(), RunError> {
+        let ip = self.ip;
+        let instruction = self.program[ip];
+
+        if let Some(r) = self.ip_register {
+            let fault = |fault| RunError::Fault { ip, fault };
+            *self
+                .registers
+                .get_mut(r)
+                .ok_or(fault(Fault::RegisterOutOfRange(r)))? =
+                W::from_usize(ip).ok_or(fault(Fault::Overflow))?;
+        }
+
+        if let Err(fault) = instruction.execute(&mut self.registers) {
+            return Err(RunError::Fault { ip, fault });
+        }
 
         self.instructions_executed += 1;
 
-        self.ip = self.registers[self.ip_register];
-        self.ip += 1;
+        // A register value that is not a valid address moves the ip outside of the program.
+        self.ip = match self.ip_register {
+            Some(r) => self.registers[r]
+                .to_usize()
+                .and_then(|ip| ip.checked_add(1))
+                .unwrap_or(usize::MAX),
+            None => ip + 1,
+        };
+
+        Ok(())
     }
 
-    pub fn run(&mut self) {
-        while self.ip < self.program.len() {
-            self.tick();
+    /// Runs until the program halts, ignoring breakpoints and watchpoints, then prints the
+    /// profile report to stderr if profiling is enabled.
+    pub fn run(&mut self) -> Result<(), RunError> {
+        let result = self.execute(None, false).map(|_| ());
+
+        if let Some(report) = self.profile_report() {
+            eprint!("{report}");
+        }
+
+        result
+    }
+
+    /// Runs until the program halts, a breakpoint or watchpoint is hit, or `budget` instructions
+    /// have been executed. At least one instruction is executed before a breakpoint can be hit
+    /// again, so calling this in a loop makes progress.
+    pub fn run_until(&mut self, budget: Option<usize>) -> Result<StopReason, RunError> {
+        self.execute(budget, true)
+    }
+
+    /// Runs like [`Self::run_until`], then flushes the tracer if there is one.
+    fn execute(&mut self, budget: Option<usize>, hooks: bool) -> Result<StopReason, RunError> {
+        let result = self.execute_unflushed(budget, hooks);
+
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.flush();
+        }
+
+        result
+    }
+
+    fn execute_unflushed(
+        &mut self,
+        budget: Option<usize>,
+        hooks: bool,
+    ) -> Result<StopReason, RunError> {
+        let start = Instant::now();
+        let mut cycles = self
+            .detect_cycles
+            .then(|| CycleDetector::new((self.ip, self.registers)));
+        let ticking = self.is_instrumented() || !self.superinstructions.is_empty();
+        let watching = hooks && !self.watchpoints.is_empty();
+        let breaking = hooks && !self.breakpoints.is_empty();
+
+        // Budget, instruction limit and clock are only looked at once `instructions_executed`
+        // reaches `next_check`, keeping the hot loop down to a single comparison.
+        let budget_end = budget.map(|budget| self.instructions_executed.saturating_add(budget));
+        let instruction_limit = self.instruction_limit;
+        let time_limit = self.time_limit;
+        let next_check = |executed: usize| {
+            [
+                budget_end,
+                instruction_limit,
+                time_limit.map(|_| executed.saturating_add(TIME_CHECK_INTERVAL)),
+            ]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(usize::MAX)
+        };
+        let mut check_at = self.instructions_executed;
+
+        loop {
+            if self.is_halted() {
+                return Ok(StopReason::Halted);
+            }
+
+            if self.instructions_executed >= check_at {
+                let executed = self.instructions_executed;
+
+                if budget_end.is_some_and(|end| executed >= end) {
+                    return Ok(StopReason::BudgetExhausted);
+                }
+
+                if let Some(limit) = instruction_limit.filter(|&limit| executed >= limit) {
+                    return Err(RunError::InstructionLimit(limit));
+                }
+
+                if let Some(limit) = time_limit.filter(|&limit| start.elapsed() >= limit) {
+                    return Err(RunError::TimeLimit(limit));
+                }
+
+                check_at = next_check(executed);
+            }
+
+            let address = self.ip;
+            // The ip register is bound to `address` first, so only writes show up as changes.
+            let before = watching.then(|| {
+                let mut before = self.registers;
+                if let Some(r) = self.ip_register.filter(|&r| r < N) {
+                    before[r] = W::from_usize(address).unwrap_or(before[r]);
+                }
+                before
+            });
+
+            if ticking {
+                self.tick()?;
+            } else {
+                self.step()?;
+            }
+
+            if let Some(detector) = cycles.as_mut() {
+                if let Some(period) = detector.check((self.ip, self.registers)) {
+                    return Err(RunError::Cycle {
+                        ip: self.ip,
+                        period,
+                    });
+                }
+            }
+
+            if let Some(before) = before {
+                // A superinstruction writes more than the output of its first instruction, so
+                // any watched register that changed counts as written too.
+                let output = self.program[address].output;
+                let written = if self.watchpoints.contains(&output) {
+                    Some(output)
+                } else {
+                    self.watchpoints
+                        .iter()
+                        .copied()
+                        .find(|&r| r < N && self.registers[r] != before[r])
+                };
+
+                if let Some(register) = written {
+                    return Ok(StopReason::Watchpoint { register, address });
+                }
+            }
+
+            if breaking && self.breakpoints.contains(&self.ip) {
+                return Ok(StopReason::Breakpoint(self.ip));
+            }
         }
     }
 }
 
-fn parse_ip_register(line: &str) -> Option<usize> {
-    line[4..].parse().ok()
+/// How an operation interprets one of its inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register,
+    Immediate,
+    Ignored,
 }
 
-fn parse_instruction(line: &str) -> Option<Instruction> {
-    let label = &line[0..4];
-    let vals = line[5..]
-        .split(' ')
-        .filter_map(|n| n.parse().ok())
-        .collect::<Vec<_>>();
+impl OpKind {
+    pub fn operand_a(self) -> Operand {
+        match self {
+            OpKind::Seti | OpKind::Gtir | OpKind::Eqir => Operand::Immediate,
+            _ => Operand::Register,
+        }
+    }
+
+    pub fn operand_b(self) -> Operand {
+        match self {
+            OpKind::Setr | OpKind::Seti => Operand::Ignored,
+            OpKind::Addi
+            | OpKind::Muli
+            | OpKind::Bani
+            | OpKind::Bori
+            | OpKind::Gtri
+            | OpKind::Eqri => Operand::Immediate,
+            _ => Operand::Register,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    MissingIpDirective,
+    InvalidNumber(String),
+    UnknownMnemonic(String),
+    WrongOperandCount {
+        expected: usize,
+        found: usize,
+    },
+    /// A register at or past the `count` registers of the machine being parsed for.
+    RegisterOutOfRange {
+        register: usize,
+        count: usize,
+    },
+    UnknownLabel(String),
+    /// A label, alias or the ip register was defined twice.
+    Redefined(String),
+    /// An alias name that would be read as a number or a register.
+    InvalidAlias(String),
+    /// An instruction annotated with an address, as in a disassembly, is not at that address.
+    AddressMismatch {
+        expected: usize,
+        found: usize,
+    },
+    /// Jumps cannot target address 0, since the ip is incremented after every instruction.
+    JumpToEntry,
+}
 
-    Some(Instruction::new(label, vals[0], vals[1], vals[2]))
+/// An error produced while parsing an ElfCode program or assembling its source. `line` and
+/// `column` are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
 }
 
-pub fn parse_computer_program(input: &str) -> Option<(usize, Program)> {
-    let mut lines = input.lines();
-    let ip_register = parse_ip_register(lines.next()?)?;
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+
+        match &self.kind {
+            ParseErrorKind::MissingIpDirective => write!(f, "expected an `#ip` directive"),
+            ParseErrorKind::InvalidNumber(s) => write!(f, "invalid number \"{s}\""),
+            ParseErrorKind::UnknownMnemonic(s) => write!(f, "unknown mnemonic \"{s}\""),
+            ParseErrorKind::WrongOperandCount { expected, found } => {
+                write!(f, "expected {expected} operands, found {found}")
+            }
+            ParseErrorKind::RegisterOutOfRange { register, count } => {
+                write!(f, "register {register} is out of range (0..{count})")
+            }
+            ParseErrorKind::UnknownLabel(s) => write!(f, "unknown label \"{s}\""),
+            ParseErrorKind::Redefined(s) => write!(f, "\"{s}\" is already defined"),
+            ParseErrorKind::InvalidAlias(s) => {
+                write!(f, "\"{s}\" is a number or register, not an alias")
+            }
+            ParseErrorKind::AddressMismatch { expected, found } => {
+                write!(f, "instruction is at address {expected}, not {found}")
+            }
+            ParseErrorKind::JumpToEntry => write!(f, "address 0 cannot be jumped to"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Splits a line into whitespace separated tokens, paired with their 1-based column.
+/// Everything after a `;` is treated as a comment.
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let code = line.split(';').next().unwrap_or_default();
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    // Columns count characters, so they match what an editor shows for non-ASCII lines.
+    for (column, (idx, ch)) in (1..).zip(code.char_indices().chain([(code.len(), ' ')])) {
+        match (ch.is_whitespace(), start) {
+            (true, Some((s, start_column))) => {
+                tokens.push((start_column, &code[s..idx]));
+                start = None;
+            }
+            (false, None) => start = Some((idx, column)),
+            _ => {}
+        }
+    }
+
+    tokens
+}
+
+fn parse_number(line: usize, (column, token): (usize, &str)) -> Result<usize, ParseError> {
+    token.parse().map_err(|_| ParseError {
+        line,
+        column,
+        kind: ParseErrorKind::InvalidNumber(token.to_owned()),
+    })
+}
+
+fn parse_register(line: usize, token: (usize, &str), count: usize) -> Result<usize, ParseError> {
+    let register = parse_number(line, token)?;
+
+    if register >= count {
+        return Err(ParseError {
+            line,
+            column: token.0,
+            kind: ParseErrorKind::RegisterOutOfRange { register, count },
+        });
+    }
+
+    Ok(register)
+}
+
+fn parse_ip_register(
+    line: usize,
+    tokens: &[(usize, &str)],
+    count: usize,
+) -> Result<usize, ParseError> {
+    match tokens {
+        [(_, "#ip"), register] => parse_register(line, *register, count),
+        [(_, "#ip"), rest @ ..] => Err(ParseError {
+            line,
+            column: tokens[0].0,
+            kind: ParseErrorKind::WrongOperandCount {
+                expected: 1,
+                found: rest.len(),
+            },
+        }),
+        _ => Err(ParseError {
+            line,
+            column: tokens[0].0,
+            kind: ParseErrorKind::MissingIpDirective,
+        }),
+    }
+}
+
+fn parse_instruction(
+    line: usize,
+    tokens: &[(usize, &str)],
+    count: usize,
+) -> Result<Instruction, ParseError> {
+    decode_instruction(line, tokens, |line, token| {
+        parse_register(line, token, count)
+    })
+}
+
+/// Decodes `op a b c`, resolving register operands with `register`.
+fn decode_instruction(
+    line: usize,
+    tokens: &[(usize, &str)],
+    register: impl Fn(usize, (usize, &str)) -> Result<usize, ParseError>,
+) -> Result<Instruction, ParseError> {
+    let (column, mnemonic) = tokens[0];
+    let op: OpKind = mnemonic.parse().map_err(|_| ParseError {
+        line,
+        column,
+        kind: ParseErrorKind::UnknownMnemonic(mnemonic.to_owned()),
+    })?;
+
+    let [a, b, c] = tokens[1..] else {
+        return Err(ParseError {
+            line,
+            column,
+            kind: ParseErrorKind::WrongOperandCount {
+                expected: 3,
+                found: tokens.len() - 1,
+            },
+        });
+    };
+
+    let input_a = match op.operand_a() {
+        Operand::Register => register(line, a)?,
+        _ => parse_number(line, a)?,
+    };
+    let input_b = match op.operand_b() {
+        Operand::Register => register(line, b)?,
+        _ => parse_number(line, b)?,
+    };
+    let output = register(line, c)?;
+
+    Ok(Instruction::new(op, input_a, input_b, output))
+}
+
+/// Parses an `#ip` header followed by one instruction per line. Blank lines and `;` comments are skipped.
+pub fn parse_computer_program(input: &str) -> Result<(usize, Program), ParseError> {
+    parse_program(input, REGISTER_COUNT)
+}
+
+/// [`parse_computer_program`] for a [`Machine`] with `count` registers.
+pub fn parse_program(input: &str, count: usize) -> Result<(usize, Program), ParseError> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, tokenize(line)))
+        .filter(|(_, tokens)| !tokens.is_empty());
+
+    let ip_register = match lines.next() {
+        Some((line, tokens)) => parse_ip_register(line, &tokens, count)?,
+        None => {
+            return Err(ParseError {
+                line: input.lines().count().max(1),
+                column: 1,
+                kind: ParseErrorKind::MissingIpDirective,
+            })
+        }
+    };
+
+    let program = lines
+        .map(|(line, tokens)| parse_instruction(line, &tokens, count))
+        .collect::<Result<_, _>>()?;
+
+    Ok((ip_register, program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::Wrapping;
+
+    #[test]
+    fn opkind_round_trips_through_display() {
+        for op in OpKind::ALL {
+            assert_eq!(op.to_string().parse::<OpKind>(), Ok(op));
+        }
+        assert!("nope".parse::<OpKind>().is_err());
+    }
+
+    #[test]
+    fn parses_program_into_decoded_instructions() {
+        let (ip_register, program) =
+            parse_computer_program("#ip 0\nseti 5 0 1\naddr 1 2 3\n").unwrap();
+        assert_eq!(ip_register, 0);
+        assert_eq!(
+            program,
+            vec![
+                Instruction::new(OpKind::Seti, 5, 0, 1),
+                Instruction::new(OpKind::Addr, 1, 2, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let input = "; header\n\n#ip 3 ; bound to r3\n  seti 5 0 1\n\n; done\n";
+        let (ip_register, program) = parse_computer_program(input).unwrap();
+        assert_eq!(ip_register, 3);
+        assert_eq!(program, vec![Instruction::new(OpKind::Seti, 5, 0, 1)]);
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        let error = |input: &str| parse_computer_program(input).unwrap_err();
+
+        assert_eq!(
+            error(""),
+            ParseError {
+                line: 1,
+                column: 1,
+                kind: ParseErrorKind::MissingIpDirective
+            }
+        );
+        assert_eq!(
+            error("seti 5 0 1"),
+            ParseError {
+                line: 1,
+                column: 1,
+                kind: ParseErrorKind::MissingIpDirective
+            }
+        );
+        assert_eq!(
+            error("#ip 0\n\n  foo 1 2 3"),
+            ParseError {
+                line: 3,
+                column: 3,
+                kind: ParseErrorKind::UnknownMnemonic("foo".to_owned())
+            }
+        );
+        assert_eq!(
+            error("#ip 0\naddr 1 2"),
+            ParseError {
+                line: 2,
+                column: 1,
+                kind: ParseErrorKind::WrongOperandCount {
+                    expected: 3,
+                    found: 2
+                }
+            }
+        );
+        assert_eq!(
+            error("#ip 0\naddi 1 9 6"),
+            ParseError {
+                line: 2,
+                column: 10,
+                kind: ParseErrorKind::RegisterOutOfRange {
+                    register: 6,
+                    count: 6
+                }
+            }
+        );
+        assert_eq!(
+            error("#ip 7").kind,
+            ParseErrorKind::RegisterOutOfRange {
+                register: 7,
+                count: 6
+            }
+        );
+        assert_eq!(
+            parse_program("#ip 5\nseti 0 0 1", 4)
+                .unwrap_err()
+                .to_string(),
+            "line 1, column 5: register 5 is out of range (0..4)"
+        );
+        assert_eq!(
+            error("#ip 0\nseti x 0 1").kind,
+            ParseErrorKind::InvalidNumber("x".to_owned())
+        );
+        // Columns count characters, not bytes.
+        assert_eq!(error("#ip 0\n\u{a0}seti x 0 1").column, 7);
+    }
+
+    fn example_computer() -> Computer {
+        let (ip_register, program) =
+            parse_computer_program(&template::read_file("examples", day!(19))).unwrap();
+        Computer::new(ip_register, program)
+    }
+
+    #[test]
+    fn stops_at_breakpoints() {
+        let mut computer = example_computer();
+        computer.add_breakpoint(4);
+
+        assert_eq!(computer.run_until(None).unwrap(), StopReason::Breakpoint(4));
+        assert_eq!(computer.instructions_executed, 3);
+        assert_eq!(computer.run_until(None).unwrap(), StopReason::Halted);
+        assert_eq!(computer.registers[0], 6);
+    }
+
+    #[test]
+    fn restores_snapshots() {
+        let mut computer = example_computer();
+        computer.add_breakpoint(4);
+        computer.run_until(None).unwrap();
+
+        let snapshot = computer.snapshot();
+        computer.run().unwrap();
+        assert_eq!(computer.registers[5], 9);
+
+        computer.restore(snapshot);
+        assert_eq!(computer.snapshot(), snapshot);
+        assert_eq!(computer.instructions_executed, 3);
+
+        // Jump over the final instruction.
+        computer.registers[1] = 6;
+        computer.run().unwrap();
+        assert_eq!(computer.registers[5], 0);
+    }
 
-    Some((ip_register, lines.filter_map(parse_instruction).collect()))
+    #[test]
+    fn forks_share_the_program() {
+        let mut computer = example_computer();
+        computer.add_breakpoint(4);
+        computer.run_until(None).unwrap();
+
+        let mut fork = computer.fork();
+        assert!(Arc::ptr_eq(&fork.program, &computer.program));
+        assert_eq!(fork.snapshot(), computer.snapshot());
+
+        fork.registers[1] = 6;
+        assert_eq!(fork.run_until(None), Ok(StopReason::Halted));
+        assert_eq!(computer.run_until(None), Ok(StopReason::Halted));
+        assert_eq!((fork.registers[5], computer.registers[5]), (0, 9));
+    }
+
+    #[test]
+    fn stops_at_watchpoints() {
+        let mut computer = example_computer();
+        computer.add_watchpoint(2);
+
+        assert_eq!(
+            computer.run_until(None).unwrap(),
+            StopReason::Watchpoint {
+                register: 2,
+                address: 1
+            }
+        );
+
+        computer.remove_watchpoint(2);
+        assert_eq!(computer.run_until(None).unwrap(), StopReason::Halted);
+    }
+
+    #[test]
+    fn stops_when_budget_is_exhausted() {
+        let mut computer = example_computer();
+
+        assert_eq!(
+            computer.run_until(Some(2)).unwrap(),
+            StopReason::BudgetExhausted
+        );
+        assert_eq!(computer.instructions_executed, 2);
+        assert_eq!(computer.run_until(Some(100)).unwrap(), StopReason::Halted);
+    }
+
+    #[test]
+    fn aborts_at_instruction_limit() {
+        let mut computer = example_computer().with_instruction_limit(2);
+
+        assert_eq!(computer.run(), Err(RunError::InstructionLimit(2)));
+        assert_eq!(computer.instructions_executed, 2);
+    }
+
+    #[test]
+    fn aborts_at_time_limit() {
+        let (ip_register, program) =
+            parse_computer_program("#ip 0\nseti 0 0 1\nseti 0 0 0").unwrap();
+        let mut computer = Computer::new(ip_register, program).with_time_limit(Duration::ZERO);
+
+        assert_eq!(computer.run(), Err(RunError::TimeLimit(Duration::ZERO)));
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let (ip_register, program) =
+            parse_computer_program("#ip 5\nseti 0 0 4\naddi 1 1 1\nbani 1 3 1\nseti 0 0 5")
+                .unwrap();
+        let mut computer = Computer::new(ip_register, program).with_cycle_detection();
+
+        match computer.run() {
+            Err(RunError::Cycle { period, .. }) => assert_eq!(period, 12),
+            other => panic!("expected a cycle, got {other:?}"),
+        }
+
+        let mut computer = example_computer().with_cycle_detection();
+        assert_eq!(computer.run(), Ok(()));
+    }
+
+    fn overflowing_program() -> (usize, Program) {
+        parse_computer_program("#ip 5\nseti 4294967296 0 1\nmulr 1 1 1").unwrap()
+    }
+
+    #[test]
+    fn reports_overflow_for_checked_words() {
+        let (ip_register, program) = overflowing_program();
+        let mut machine = Machine::<u64, 6>::new(ip_register, program);
+
+        assert_eq!(
+            machine.run(),
+            Err(RunError::Fault {
+                ip: 1,
+                fault: Fault::Overflow
+            })
+        );
+        assert_eq!(machine.ip, 1);
+        assert_eq!(machine.registers[1], 1 << 32);
+    }
+
+    #[test]
+    fn wraps_for_wrapping_words() {
+        let (ip_register, program) = overflowing_program();
+        let mut machine = Machine::<Wrapping<u64>, 6>::new(ip_register, program);
+
+        assert_eq!(machine.run(), Ok(()));
+        assert_eq!(machine.registers[1], Wrapping(0));
+    }
+
+    #[test]
+    fn runs_unbound_programs_on_fewer_registers() {
+        let program = vec![
+            Instruction::new(OpKind::Seti, 7, 0, 3),
+            Instruction::new(OpKind::Addi, 3, 2, 0),
+        ];
+        let mut machine = Machine::<i64, 4>::unbound(program);
+
+        assert_eq!(machine.run(), Ok(()));
+        assert_eq!(machine.registers, [9, 0, 0, 7]);
+
+        let program = vec![Instruction::new(OpKind::Seti, 7, 0, 5)];
+        let mut machine = Machine::<usize, 4>::unbound(program);
+        assert_eq!(
+            machine.run(),
+            Err(RunError::Fault {
+                ip: 0,
+                fault: Fault::RegisterOutOfRange(5)
+            })
+        );
+    }
+
+    #[test]
+    fn faults_on_an_ip_register_past_the_last_register() {
+        let program = vec![Instruction::new(OpKind::Seti, 7, 0, 0)];
+        let mut machine = Machine::<usize, 4>::new(5, program).with_profile();
+
+        assert_eq!(
+            machine.run(),
+            Err(RunError::Fault {
+                ip: 0,
+                fault: Fault::RegisterOutOfRange(5)
+            })
+        );
+        assert_eq!(machine.registers, [0; 4]);
+    }
 }
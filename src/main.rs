@@ -7,6 +7,7 @@ use advent_of_code::template::Day;
 use std::process;
 
 mod args {
+    use advent_of_code::template::commands::solve::Visualize;
     use advent_of_code::template::Day;
     use std::process;
 
@@ -26,14 +27,20 @@ mod args {
             release: bool,
             dhat: bool,
             submit: Option<u8>,
+            visualize: Option<Visualize>,
         },
         All {
             release: bool,
+            jobs: Option<usize>,
         },
         Time {
             all: bool,
             day: Option<Day>,
             store: bool,
+            jobs: Option<usize>,
+            budget: Option<u64>,
+            compare: bool,
+            threshold: Option<f64>,
         },
         #[cfg(feature = "today")]
         Today,
@@ -45,15 +52,24 @@ mod args {
         let app_args = match args.subcommand()?.as_deref() {
             Some("all") => AppArguments::All {
                 release: args.contains("--release"),
+                jobs: args.opt_value_from_str("--jobs")?,
             },
             Some("time") => {
                 let all = args.contains("--all");
                 let store = args.contains("--store");
+                let jobs = args.opt_value_from_str("--jobs")?;
+                let budget = args.opt_value_from_str("--budget")?;
+                let compare = args.contains("--compare");
+                let threshold = args.opt_value_from_str("--threshold")?;
 
                 AppArguments::Time {
                     all,
                     day: args.opt_free_from_str()?,
                     store,
+                    jobs,
+                    budget,
+                    compare,
+                    threshold,
                 }
             }
             Some("download") => AppArguments::Download {
@@ -71,6 +87,14 @@ mod args {
                 release: args.contains("--release"),
                 submit: args.opt_value_from_str("--submit")?,
                 dhat: args.contains("--dhat"),
+                visualize: if args.contains("--visualize") {
+                    Some(Visualize {
+                        frames: args.opt_value_from_str("--frames")?,
+                        viewport: args.opt_value_from_str("--viewport")?,
+                    })
+                } else {
+                    None
+                },
             },
             #[cfg(feature = "today")]
             Some("today") => AppArguments::Today,
@@ -100,8 +124,16 @@ fn main() {
             std::process::exit(1);
         }
         Ok(args) => match args {
-            AppArguments::All { release } => all::handle(release),
-            AppArguments::Time { day, all, store } => time::handle(day, all, store),
+            AppArguments::All { release, jobs } => all::handle(release, jobs),
+            AppArguments::Time {
+                day,
+                all,
+                store,
+                jobs,
+                budget,
+                compare,
+                threshold,
+            } => time::handle(day, all, store, jobs, budget, compare, threshold),
             AppArguments::Download { day } => download::handle(day),
             AppArguments::Read { day } => read::handle(day),
             AppArguments::Scaffold { day, download } => {
@@ -115,7 +147,8 @@ fn main() {
                 release,
                 dhat,
                 submit,
-            } => solve::handle(day, release, dhat, submit),
+                visualize,
+            } => solve::handle(day, release, dhat, submit, visualize),
             #[cfg(feature = "today")]
             AppArguments::Today => {
                 match Day::today() {
@@ -0,0 +1,658 @@
+//! Works out which opcode number belongs to which operation from before/after samples.
+
+use std::fmt::{self, Write};
+
+use crate::{tokenize, Instruction, OpKind};
+
+/// The number of registers on the device the samples were taken from.
+pub const DEVICE_REGISTERS: usize = 4;
+
+/// One observed instruction, with the registers before and after it ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub before: [usize; DEVICE_REGISTERS],
+    /// The opcode number followed by the three operands.
+    pub instruction: [usize; 4],
+    pub after: [usize; DEVICE_REGISTERS],
+}
+
+impl Snapshot {
+    pub fn opcode(&self) -> usize {
+        self.instruction[0]
+    }
+
+    /// Whether running `op` with this sample's operands explains the change in registers.
+    pub fn matches(&self, op: OpKind) -> bool {
+        let [_, a, b, c] = self.instruction;
+        let mut registers = self.before;
+
+        op.execute(&mut registers, a, b, c).is_ok() && registers == self.after
+    }
+
+    /// Every operation this sample could have been.
+    pub fn candidates(&self) -> impl Iterator<Item = OpKind> + '_ {
+        OpKind::ALL.into_iter().filter(|&op| self.matches(op))
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [opcode, a, b, c] = self.instruction;
+
+        writeln!(f, "Before: {:?}", self.before)?;
+        writeln!(f, "{opcode} {a} {b} {c}")?;
+        write!(f, "After:  {:?}", self.after)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SampleErrorKind {
+    /// Something else was found where the named part of a sample was expected.
+    Expected(&'static str),
+    InvalidNumber(String),
+    WrongRegisterCount {
+        expected: usize,
+        found: usize,
+    },
+    WrongOperandCount {
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// An error produced while parsing the day 16 samples. `line` and `column` are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: SampleErrorKind,
+}
+
+impl fmt::Display for SampleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+
+        match &self.kind {
+            SampleErrorKind::Expected(what) => write!(f, "expected {what}"),
+            SampleErrorKind::InvalidNumber(s) => write!(f, "invalid number \"{s}\""),
+            SampleErrorKind::WrongRegisterCount { expected, found } => {
+                write!(f, "expected {expected} registers, found {found}")
+            }
+            SampleErrorKind::WrongOperandCount { expected, found } => {
+                write!(f, "expected {expected} operands, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SampleError {}
+
+fn parse_number(line: usize, (column, token): (usize, &str)) -> Result<usize, SampleError> {
+    token.parse().map_err(|_| SampleError {
+        line,
+        column,
+        kind: SampleErrorKind::InvalidNumber(token.to_owned()),
+    })
+}
+
+/// The unparsed rest of a line, with the 1-based column it starts at.
+#[derive(Clone, Copy)]
+struct Cursor<'a> {
+    rest: &'a str,
+    column: usize,
+}
+
+impl Cursor<'_> {
+    /// Moves past the first `bytes` bytes of `rest`, counting columns in characters.
+    fn advance(&mut self, bytes: usize) {
+        let (skipped, rest) = self.rest.split_at(bytes);
+        self.column += skipped.chars().count();
+        self.rest = rest;
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.advance(self.rest.len() - self.rest.trim_start().len());
+    }
+
+    /// Moves past `prefix` if `rest` starts with it.
+    fn eat(&mut self, prefix: &str) -> bool {
+        let found = self.rest.starts_with(prefix);
+        if found {
+            self.advance(prefix.len());
+        }
+        found
+    }
+}
+
+/// Parses a `Before:` or `After:` line, `what` being the label in backticks.
+fn parse_registers(
+    line: usize,
+    text: &str,
+    what: &'static str,
+) -> Result<[usize; DEVICE_REGISTERS], SampleError> {
+    let error = |column, kind| SampleError { line, column, kind };
+    let mut cursor = Cursor {
+        rest: text,
+        column: 1,
+    };
+
+    cursor.skip_whitespace();
+    if !cursor.eat(what.trim_matches('`')) {
+        return Err(error(cursor.column, SampleErrorKind::Expected(what)));
+    }
+
+    cursor.skip_whitespace();
+    let open = cursor.column;
+    if !cursor.eat("[") {
+        return Err(error(open, SampleErrorKind::Expected("`[`")));
+    }
+
+    let Some(end) = cursor.rest.find(']') else {
+        return Err(error(
+            text.trim_end().chars().count() + 1,
+            SampleErrorKind::Expected("`]`"),
+        ));
+    };
+
+    let mut tail = cursor;
+    tail.advance(end + 1);
+    tail.skip_whitespace();
+    if !tail.rest.is_empty() {
+        return Err(error(tail.column, SampleErrorKind::Expected("end of line")));
+    }
+
+    let mut values = Vec::new();
+    if !cursor.rest[..end].trim().is_empty() {
+        loop {
+            cursor.skip_whitespace();
+            // The list ends at the `]` found above.
+            let len = cursor.rest.find([',', ']']).unwrap_or(cursor.rest.len());
+            let token = cursor.rest[..len].trim_end();
+            values.push(parse_number(line, (cursor.column, token))?);
+
+            cursor.advance(len);
+            if !cursor.eat(",") {
+                break;
+            }
+        }
+    }
+
+    let found = values.len();
+    values.try_into().map_err(|_| {
+        error(
+            open,
+            SampleErrorKind::WrongRegisterCount {
+                expected: DEVICE_REGISTERS,
+                found,
+            },
+        )
+    })
+}
+
+/// Parses an `opcode a b c` line.
+fn parse_encoded(line: usize, text: &str) -> Result<[usize; 4], SampleError> {
+    let tokens = tokenize(text);
+
+    let [opcode, a, b, c] = tokens[..] else {
+        return Err(SampleError {
+            line,
+            column: tokens.first().map_or(1, |&(column, _)| column),
+            kind: SampleErrorKind::WrongOperandCount {
+                expected: 3,
+                found: tokens.len().saturating_sub(1),
+            },
+        });
+    };
+
+    Ok([
+        parse_number(line, opcode)?,
+        parse_number(line, a)?,
+        parse_number(line, b)?,
+        parse_number(line, c)?,
+    ])
+}
+
+/// Parses the day 16 input: samples made of a `Before:` line, an encoded instruction and an
+/// `After:` line, followed by the encoded test program. Blank lines are ignored, so the samples
+/// end at the first line that does not start with `Before:`.
+pub fn parse_samples(input: &str) -> Result<(Vec<Snapshot>, Vec<[usize; 4]>), SampleError> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+
+    let end_of_input = |what| SampleError {
+        line: input.lines().count().max(1),
+        column: 1,
+        kind: SampleErrorKind::Expected(what),
+    };
+
+    let mut snapshots = Vec::new();
+
+    while let Some((line, text)) =
+        lines.next_if(|(_, text)| text.trim_start().starts_with("Before:"))
+    {
+        let before = parse_registers(line, text, "`Before:`")?;
+
+        let (line, text) = lines.next().ok_or_else(|| end_of_input("an instruction"))?;
+        let instruction = parse_encoded(line, text)?;
+
+        let (line, text) = lines.next().ok_or_else(|| end_of_input("`After:`"))?;
+        let after = parse_registers(line, text, "`After:`")?;
+
+        snapshots.push(Snapshot {
+            before,
+            instruction,
+            after,
+        });
+    }
+
+    let program = lines
+        .map(|(line, text)| parse_encoded(line, text))
+        .collect::<Result<_, _>>()?;
+
+    Ok((snapshots, program))
+}
+
+/// Renders samples and a test program in the puzzle input format.
+pub fn emit_samples(snapshots: &[Snapshot], program: &[[usize; 4]]) -> String {
+    let mut output = String::new();
+
+    for snapshot in snapshots {
+        let _ = writeln!(output, "{snapshot}\n");
+    }
+    output.push_str("\n\n");
+
+    for [opcode, a, b, c] in program {
+        let _ = writeln!(output, "{opcode} {a} {b} {c}");
+    }
+
+    output
+}
+
+/// `rounds` samples per opcode number, opcode `n` being `ops[n]`, with registers and operands
+/// drawn from a fixed pseudo-random sequence. Meant for tests, where a handful of rounds is
+/// enough for [`solve`] to pin down every opcode.
+#[cfg(any(test, feature = "test_lib"))]
+pub fn random_samples(ops: [OpKind; 16], rounds: usize) -> Vec<Snapshot> {
+    let mut seed = 17_usize;
+    let mut next = |bound: usize| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) % bound
+    };
+
+    let mut samples = Vec::new();
+    for _ in 0..rounds {
+        for (opcode, op) in ops.into_iter().enumerate() {
+            let before = [next(16), next(16), next(16), next(16)];
+            let [a, b, c] = [next(4), next(4), next(4)];
+
+            let mut after = before;
+            // Operands are valid registers and every result fits a `usize`.
+            op.execute(&mut after, a, b, c).unwrap();
+            samples.push(Snapshot {
+                before,
+                instruction: [opcode, a, b, c],
+                after,
+            });
+        }
+    }
+    samples
+}
+
+/// A set of operations, one bit per position in [`OpKind::ALL`].
+type OpSet = u16;
+
+const ALL_OPS: OpSet = OpSet::MAX;
+
+fn bit(op: OpKind) -> OpSet {
+    1 << op as usize
+}
+
+fn ops(set: OpSet) -> Vec<OpKind> {
+    OpKind::ALL
+        .into_iter()
+        .filter(|&op| set & bit(op) != 0)
+        .collect()
+}
+
+/// The operation behind every opcode number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeTable([OpKind; 16]);
+
+impl OpcodeTable {
+    pub fn get(&self, opcode: usize) -> Option<OpKind> {
+        self.0.get(opcode).copied()
+    }
+
+    /// Turns `[opcode, a, b, c]` into an instruction, if the opcode number is known.
+    pub fn decode(&self, [opcode, a, b, c]: [usize; 4]) -> Option<Instruction> {
+        Some(Instruction::new(self.get(opcode)?, a, b, c))
+    }
+}
+
+/// Why [`solve`] could not produce a table. Samples are identified by their index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// The sample uses an opcode number outside `0..16`.
+    OpcodeOutOfRange { sample: usize, opcode: usize },
+    /// Every operation was ruled out for `opcode`. `samples` are the ones that narrowed it down.
+    NoOperation { opcode: usize, samples: Vec<usize> },
+    /// Every opcode number was ruled out for `op`.
+    NoOpcode(OpKind),
+    /// The samples do not pin down these opcode numbers. `samples` are the ones that narrowed
+    /// them down.
+    Ambiguous {
+        candidates: Vec<(usize, Vec<OpKind>)>,
+        samples: Vec<usize>,
+    },
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveError::OpcodeOutOfRange { sample, opcode } => {
+                write!(f, "sample {sample} uses opcode {opcode}, expected 0..16")
+            }
+            SolveError::NoOperation { opcode, samples } => {
+                write!(
+                    f,
+                    "no operation fits opcode {opcode}, see samples {samples:?}"
+                )
+            }
+            SolveError::NoOpcode(op) => write!(f, "no opcode is left for {op}"),
+            SolveError::Ambiguous {
+                candidates,
+                samples,
+            } => {
+                write!(f, "ambiguous opcodes")?;
+                for (opcode, ops) in candidates {
+                    let names = ops.iter().map(|op| op.mnemonic()).collect::<Vec<_>>();
+                    write!(f, ", {opcode} could be {}", names.join("/"))?;
+                }
+                write!(f, ", see samples {samples:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// Narrows `candidates` until nothing changes. An opcode with a single candidate rules that
+/// operation out everywhere else, and an operation that fits a single opcode is assigned to it.
+fn propagate(candidates: &mut [OpSet; 16]) -> Result<(), SolveError> {
+    loop {
+        let mut changed = false;
+
+        for opcode in 0..candidates.len() {
+            let set = candidates[opcode];
+            if set.count_ones() != 1 {
+                continue;
+            }
+
+            for (other, others) in candidates.iter_mut().enumerate() {
+                if other != opcode && *others & set != 0 {
+                    *others &= !set;
+                    changed = true;
+                }
+            }
+        }
+
+        if let Some(opcode) = candidates.iter().position(|&set| set == 0) {
+            return Err(SolveError::NoOperation {
+                opcode,
+                samples: Vec::new(),
+            });
+        }
+
+        for op in OpKind::ALL {
+            let mut fits =
+                (0..candidates.len()).filter(|&opcode| candidates[opcode] & bit(op) != 0);
+
+            match (fits.next(), fits.next()) {
+                (None, _) => return Err(SolveError::NoOpcode(op)),
+                (Some(opcode), None) if candidates[opcode] != bit(op) => {
+                    candidates[opcode] = bit(op);
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+
+        if !changed {
+            return Ok(());
+        }
+    }
+}
+
+/// Finds the only opcode table consistent with every sample.
+pub fn solve(snapshots: &[Snapshot]) -> Result<OpcodeTable, SolveError> {
+    let mut candidates = [ALL_OPS; 16];
+    let mut narrowed_by = vec![Vec::new(); 16];
+
+    for (sample, snapshot) in snapshots.iter().enumerate() {
+        let opcode = snapshot.opcode();
+        let Some(set) = candidates.get_mut(opcode) else {
+            return Err(SolveError::OpcodeOutOfRange { sample, opcode });
+        };
+
+        let fits = snapshot.candidates().fold(0, |set, op| set | bit(op));
+        if *set & !fits != 0 {
+            *set &= fits;
+            narrowed_by[opcode].push(sample);
+        }
+    }
+
+    propagate(&mut candidates).map_err(|error| match error {
+        SolveError::NoOperation { opcode, .. } => SolveError::NoOperation {
+            opcode,
+            samples: narrowed_by[opcode].clone(),
+        },
+        error => error,
+    })?;
+
+    let undecided = (0..16)
+        .filter(|&opcode| candidates[opcode].count_ones() > 1)
+        .collect::<Vec<_>>();
+
+    if !undecided.is_empty() {
+        let mut samples = undecided
+            .iter()
+            .flat_map(|&opcode| narrowed_by[opcode].iter().copied())
+            .collect::<Vec<_>>();
+        samples.sort_unstable();
+
+        return Err(SolveError::Ambiguous {
+            candidates: undecided
+                .into_iter()
+                .map(|opcode| (opcode, ops(candidates[opcode])))
+                .collect(),
+            samples,
+        });
+    }
+
+    Ok(OpcodeTable(
+        candidates.map(|set| OpKind::ALL[set.trailing_zeros() as usize]),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{read_file, Day};
+
+    /// Opcode `n` is `OpKind::ALL[SECRET[n]]`.
+    const SECRET: [usize; 16] = [7, 12, 3, 0, 15, 9, 1, 4, 13, 2, 10, 6, 14, 8, 11, 5];
+
+    fn sample(opcode: usize, before: [usize; 4], a: usize, b: usize, c: usize) -> Snapshot {
+        let mut after = before;
+        OpKind::ALL[SECRET[opcode]]
+            .execute(&mut after, a, b, c)
+            .unwrap();
+
+        Snapshot {
+            before,
+            instruction: [opcode, a, b, c],
+            after,
+        }
+    }
+
+    /// A few samples per opcode with varied registers and operands.
+    fn samples() -> Vec<Snapshot> {
+        random_samples(SECRET.map(|op| OpKind::ALL[op]), 8)
+    }
+
+    #[test]
+    fn recovers_the_table() {
+        let table = solve(&samples()).unwrap();
+
+        for (opcode, &op) in SECRET.iter().enumerate() {
+            assert_eq!(table.get(opcode), Some(OpKind::ALL[op]));
+        }
+        assert_eq!(table.get(16), None);
+    }
+
+    #[test]
+    fn propagates_hidden_singles() {
+        // Opcodes 0 to 2 could all be addr or addi, but only opcode 1 can be mulr.
+        let mut candidates = [ALL_OPS; 16];
+        for (opcode, set) in candidates.iter_mut().enumerate().skip(3) {
+            *set = bit(OpKind::ALL[opcode]);
+        }
+        candidates[0] = bit(OpKind::Addr) | bit(OpKind::Addi);
+        candidates[1] = bit(OpKind::Addr) | bit(OpKind::Addi) | bit(OpKind::Mulr);
+        candidates[2] = bit(OpKind::Addr) | bit(OpKind::Addi);
+
+        assert_eq!(propagate(&mut candidates), Ok(()));
+        assert_eq!(candidates[1], bit(OpKind::Mulr));
+        assert_eq!(candidates[0], bit(OpKind::Addr) | bit(OpKind::Addi));
+    }
+
+    #[test]
+    fn reports_ambiguity_and_contradictions() {
+        // One sample per opcode is not enough to tell them all apart.
+        let snapshots = &samples()[..16];
+
+        let Err(SolveError::Ambiguous {
+            candidates,
+            samples: narrowing,
+        }) = solve(snapshots)
+        else {
+            panic!("expected ambiguity");
+        };
+        for (opcode, ops) in &candidates {
+            assert!(ops.len() > 1);
+            assert!(ops.contains(&OpKind::ALL[SECRET[*opcode]]));
+        }
+        assert!(narrowing.iter().all(|&s| candidates
+            .iter()
+            .any(|(opcode, _)| snapshots[s].opcode() == *opcode)));
+
+        // Nothing sets the first register to 99.
+        let mut snapshots = samples();
+        snapshots.push(Snapshot {
+            before: [0; 4],
+            instruction: [5, 0, 0, 0],
+            after: [99, 0, 0, 0],
+        });
+        let last = snapshots.len() - 1;
+
+        let Err(SolveError::NoOperation { opcode, samples }) = solve(&snapshots) else {
+            panic!("expected a contradiction");
+        };
+        assert_eq!(opcode, 5);
+        assert_eq!(samples.last(), Some(&last));
+
+        assert_eq!(
+            solve(&[
+                sample(2, [0; 4], 0, 0, 0),
+                Snapshot {
+                    before: [0; 4],
+                    instruction: [16, 0, 0, 0],
+                    after: [0; 4],
+                }
+            ]),
+            Err(SolveError::OpcodeOutOfRange {
+                sample: 1,
+                opcode: 16
+            })
+        );
+    }
+
+    #[test]
+    fn round_trips_samples() {
+        let snapshots = samples();
+        let program = vec![[3, 1, 2, 0], [15, 0, 0, 1]];
+        let text = emit_samples(&snapshots, &program);
+
+        let parsed = parse_samples(&text).unwrap();
+        assert_eq!(parsed, (snapshots.clone(), program.clone()));
+
+        // Windows line endings and stray blank lines do not matter.
+        let messy = text.replace("\n\n", "\n\n\n").replace('\n', "\r\n");
+        assert_eq!(parse_samples(&messy).unwrap(), parsed);
+
+        let example = read_file("examples", Day::new(16).unwrap());
+        let (snapshots, program) = parse_samples(&example).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert!(program.is_empty());
+        assert_eq!(snapshots[0].to_string(), example.trim_end());
+    }
+
+    #[test]
+    fn reports_malformed_samples() {
+        let error = |text: &str| parse_samples(text).unwrap_err();
+
+        assert_eq!(
+            error("Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, x, 1]\n"),
+            SampleError {
+                line: 3,
+                column: 16,
+                kind: SampleErrorKind::InvalidNumber("x".to_owned())
+            }
+        );
+        // Columns count characters, not bytes.
+        assert_eq!(
+            error("Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:\u{a0}[3, 2, x, 1]\n"),
+            SampleError {
+                line: 3,
+                column: 15,
+                kind: SampleErrorKind::InvalidNumber("x".to_owned())
+            }
+        );
+        assert_eq!(
+            error("Before: [3, 2, 1, 1]\n\n9 2 1 2\n\nAfter: 3, 2, 2, 1\n"),
+            SampleError {
+                line: 5,
+                column: 8,
+                kind: SampleErrorKind::Expected("`[`")
+            }
+        );
+        assert_eq!(
+            error("Before: [3, 2, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]\n").kind,
+            SampleErrorKind::WrongRegisterCount {
+                expected: 4,
+                found: 3
+            }
+        );
+        assert_eq!(
+            error("Before: [3, 2, 1, 1]\n9 2 1 2\n"),
+            SampleError {
+                line: 2,
+                column: 1,
+                kind: SampleErrorKind::Expected("`After:`")
+            }
+        );
+        assert_eq!(
+            error("\n\n9 2 1\n").kind,
+            SampleErrorKind::WrongOperandCount {
+                expected: 3,
+                found: 2
+            }
+        );
+    }
+}
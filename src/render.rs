@@ -0,0 +1,310 @@
+//! Text frames of grid simulations, optionally coloured, and sinks that animate or save them.
+
+use std::fmt::Write as _;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::Duration;
+use std::{env, fs};
+
+use glam::IVec2;
+
+use crate::grid::Bounds;
+use crate::template::runner::flag_value;
+use crate::template::ANSI_RESET;
+
+const ANSI_CLEAR: &str = "\x1b[H\x1b[2J";
+
+/// How long each frame stays on screen when animating in the terminal.
+const FRAME_DELAY: Duration = Duration::from_millis(80);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colour {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Grey,
+}
+
+impl Colour {
+    fn ansi(self) -> &'static str {
+        match self {
+            Colour::Red => "\x1b[31m",
+            Colour::Green => "\x1b[32m",
+            Colour::Yellow => "\x1b[33m",
+            Colour::Blue => "\x1b[34m",
+            Colour::Magenta => "\x1b[35m",
+            Colour::Cyan => "\x1b[36m",
+            Colour::Grey => "\x1b[90m",
+        }
+    }
+}
+
+/// What to draw for one cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyph {
+    pub symbol: char,
+    pub colour: Option<Colour>,
+}
+
+impl Glyph {
+    pub fn plain(symbol: char) -> Self {
+        Self {
+            symbol,
+            colour: None,
+        }
+    }
+
+    pub fn coloured(symbol: char, colour: Colour) -> Self {
+        Self {
+            symbol,
+            colour: Some(colour),
+        }
+    }
+}
+
+/// A scene that can be drawn as a frame of text, one glyph per position.
+pub trait Renderer {
+    /// The area worth drawing, or `None` if the scene is empty.
+    fn bounds(&self) -> Option<Bounds>;
+
+    fn glyph(&self, pos: IVec2) -> Glyph;
+
+    /// A line printed above the frame.
+    fn caption(&self) -> Option<String> {
+        None
+    }
+
+    /// Text printed to the right of row `y`.
+    fn annotate_row(&self, _y: i32) -> Option<String> {
+        None
+    }
+
+    /// Draws the scene, cropped to `viewport` if given, with ANSI colours if `colour` is set.
+    fn render(&self, viewport: Option<Bounds>, colour: bool) -> String {
+        let mut frame = String::new();
+
+        if let Some(caption) = self.caption() {
+            frame.push_str(&caption);
+            frame.push('\n');
+        }
+
+        let Some(bounds) = viewport.or_else(|| self.bounds()) else {
+            return frame;
+        };
+
+        for y in bounds.min.y..=bounds.max.y {
+            for x in bounds.min.x..=bounds.max.x {
+                let glyph = self.glyph(IVec2::new(x, y));
+
+                match glyph.colour {
+                    Some(c) if colour => {
+                        let _ = write!(frame, "{}{}{ANSI_RESET}", c.ansi(), glyph.symbol);
+                    }
+                    _ => frame.push(glyph.symbol),
+                }
+            }
+
+            if let Some(annotation) = self.annotate_row(y) {
+                frame.push_str("   ");
+                frame.push_str(&annotation);
+            }
+
+            frame.push('\n');
+        }
+
+        frame
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkTarget {
+    /// Redraw each frame in place, pausing between frames.
+    Terminal { delay: Duration },
+    /// Write each frame to its own numbered file in a directory. Numbering continues after any
+    /// frames already there, so both parts of a day can share one directory.
+    Directory(PathBuf),
+}
+
+/// Receives the frames of a simulation. Write errors are ignored so visualizing never changes
+/// the outcome of a run.
+#[derive(Debug, Clone)]
+pub struct FrameSink {
+    target: SinkTarget,
+    viewport: Option<Bounds>,
+    frames: usize,
+    /// The number of the first frame, once known.
+    first_frame: Option<usize>,
+}
+
+impl FrameSink {
+    pub fn new(target: SinkTarget) -> Self {
+        Self {
+            target,
+            viewport: None,
+            frames: 0,
+            first_frame: None,
+        }
+    }
+
+    /// Crops every frame to `viewport`.
+    #[must_use]
+    pub fn with_viewport(mut self, viewport: Bounds) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    /// Builds a sink from `--visualize [--frames <dir>] [--viewport <x0,y0,x1,y1>]`, or `None`
+    /// if `--visualize` was not passed. Called once by the `main` of each day, which hands the
+    /// sink to the parts that draw frames.
+    pub fn from_args() -> Option<Self> {
+        let args = env::args().collect::<Vec<_>>();
+
+        if !args.iter().any(|x| x == "--visualize") {
+            return None;
+        }
+
+        let target = match flag_value(&args, "--frames") {
+            Some(dir) => SinkTarget::Directory(PathBuf::from(dir)),
+            None => SinkTarget::Terminal { delay: FRAME_DELAY },
+        };
+
+        let mut sink = Self::new(target);
+
+        if let Some(viewport) = flag_value(&args, "--viewport") {
+            match parse_viewport(viewport) {
+                Some(viewport) => sink = sink.with_viewport(viewport),
+                None => eprintln!("Ignoring invalid viewport {viewport:?}, expected x0,y0,x1,y1."),
+            }
+        }
+
+        Some(sink)
+    }
+
+    /// How many frames have been emitted so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn emit(&mut self, scene: &impl Renderer) {
+        self.frames += 1;
+
+        match &self.target {
+            SinkTarget::Terminal { delay } => {
+                let frame = scene.render(self.viewport, true);
+                let mut stdout = stdout();
+                let _ = write!(stdout, "{ANSI_CLEAR}{frame}");
+                let _ = stdout.flush();
+                sleep(*delay);
+            }
+            SinkTarget::Directory(dir) => {
+                let frame = scene.render(self.viewport, false);
+                let _ = fs::create_dir_all(dir);
+                let first = *self
+                    .first_frame
+                    .get_or_insert_with(|| existing_frames(dir) + 1);
+                let path = dir.join(format!("frame-{:05}.txt", first + self.frames - 1));
+                let _ = fs::write(path, frame);
+            }
+        }
+    }
+}
+
+fn existing_frames(dir: &Path) -> usize {
+    fs::read_dir(dir).map_or(0, |entries| {
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("frame-"))
+            .count()
+    })
+}
+
+fn parse_viewport(s: &str) -> Option<Bounds> {
+    let corners = s
+        .split(',')
+        .map(|n| n.trim().parse().ok())
+        .collect::<Option<Vec<i32>>>()?;
+
+    match corners[..] {
+        [x0, y0, x1, y1] if x0 <= x1 && y0 <= y1 => {
+            Some(Bounds::new(IVec2::new(x0, y0), IVec2::new(x1, y1)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A diagonal line of lit cells.
+    struct Diagonal(i32);
+
+    impl Renderer for Diagonal {
+        fn bounds(&self) -> Option<Bounds> {
+            Some(Bounds::new(IVec2::ZERO, IVec2::splat(self.0 - 1)))
+        }
+
+        fn glyph(&self, pos: IVec2) -> Glyph {
+            if pos.x == pos.y {
+                Glyph::coloured('#', Colour::Red)
+            } else {
+                Glyph::plain('.')
+            }
+        }
+
+        fn caption(&self) -> Option<String> {
+            Some(format!("size {}", self.0))
+        }
+
+        fn annotate_row(&self, y: i32) -> Option<String> {
+            (y == 0).then(|| "top".to_owned())
+        }
+    }
+
+    #[test]
+    fn renders_crops_and_colours_frames() {
+        assert_eq!(
+            Diagonal(3).render(None, false),
+            "size 3\n#..   top\n.#.\n..#\n"
+        );
+        assert_eq!(
+            Diagonal(3).render(Some(Bounds::new(IVec2::ONE, IVec2::new(3, 1))), false),
+            "size 3\n#..\n"
+        );
+        assert_eq!(
+            Diagonal(1).render(None, true),
+            "size 1\n\x1b[31m#\x1b[0m   top\n"
+        );
+        assert_eq!(
+            parse_viewport("-1,0, 4,2"),
+            Some(Bounds::new(IVec2::new(-1, 0), IVec2::new(4, 2)))
+        );
+        assert_eq!(parse_viewport("4,0,1,2"), None);
+        assert_eq!(parse_viewport("1,2,3"), None);
+    }
+
+    #[test]
+    fn writes_numbered_frames() {
+        let dir = env::temp_dir().join(format!("aoc-frames-{}", std::process::id()));
+        let mut sink = FrameSink::new(SinkTarget::Directory(dir.clone()));
+
+        sink.emit(&Diagonal(1));
+        sink.emit(&Diagonal(2));
+
+        assert_eq!(sink.frames(), 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("frame-00002.txt")).unwrap(),
+            "size 2\n#.   top\n.#\n"
+        );
+
+        let mut sink = FrameSink::new(SinkTarget::Directory(dir.clone()));
+        sink.emit(&Diagonal(3));
+        assert!(dir.join("frame-00003.txt").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
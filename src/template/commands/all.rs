@@ -1,5 +1,8 @@
 use crate::template::{all_days, run_multi::run_multi};
 
-pub fn handle(is_release: bool) {
-    run_multi(&all_days().collect(), is_release, false);
+/// Runs every day, one at a time unless `jobs` says otherwise.
+pub fn handle(is_release: bool, jobs: Option<usize>) {
+    let jobs = jobs.unwrap_or(1);
+
+    run_multi(&all_days().collect(), is_release, None, jobs);
 }
@@ -2,7 +2,21 @@ use std::process::{Command, Stdio};
 
 use crate::template::Day;
 
-pub fn handle(day: Day, release: bool, dhat: bool, submit_part: Option<u8>) {
+/// Options forwarded to the solution binary for `--visualize`.
+pub struct Visualize {
+    /// A directory to write numbered frames to, instead of animating in the terminal.
+    pub frames: Option<String>,
+    /// The area to crop frames to, as `x0,y0,x1,y1`.
+    pub viewport: Option<String>,
+}
+
+pub fn handle(
+    day: Day,
+    release: bool,
+    dhat: bool,
+    submit_part: Option<u8>,
+    visualize: Option<Visualize>,
+) {
     let mut cmd_args = vec!["run".to_string(), "--bin".to_string(), day.to_string()];
 
     if dhat {
@@ -23,6 +37,18 @@ pub fn handle(day: Day, release: bool, dhat: bool, submit_part: Option<u8>) {
         cmd_args.push(submit_part.to_string());
     }
 
+    if let Some(visualize) = visualize {
+        cmd_args.push("--visualize".to_string());
+
+        if let Some(frames) = visualize.frames {
+            cmd_args.extend(["--frames".to_string(), frames]);
+        }
+
+        if let Some(viewport) = visualize.viewport {
+            cmd_args.extend(["--viewport".to_string(), viewport]);
+        }
+    }
+
     let mut cmd = Command::new("cargo")
         .args(&cmd_args)
         .stdout(Stdio::inherit())
@@ -1,16 +1,47 @@
 use std::collections::HashSet;
+use std::process;
+use std::time::Duration;
 
+use crate::template::compare::{self, DEFAULT_THRESHOLD};
 use crate::template::run_multi::run_multi;
+use crate::template::runner::DEFAULT_BENCH_BUDGET;
 use crate::template::timings::Timings;
 use crate::template::{all_days, readme_benchmarks, Day};
 
-pub fn handle(day: Option<Day>, run_all: bool, store: bool) {
+/// Benchmarks days one at a time unless `jobs` says otherwise, so they don't skew each other.
+/// Each part is benchmarked for `budget_ms`, or [`DEFAULT_BENCH_BUDGET`].
+///
+/// With `compare`, the results are checked against the stored timings and the process exits
+/// with an error if any part got slower by more than `threshold` percent.
+pub fn handle(
+    day: Option<Day>,
+    run_all: bool,
+    store: bool,
+    jobs: Option<usize>,
+    budget_ms: Option<u64>,
+    compare: bool,
+    threshold: Option<f64>,
+) {
+    let jobs = jobs.unwrap_or(1);
+    let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD);
+    if jobs > 1 {
+        eprintln!("Timing {jobs} days at once, benchmarks may be skewed.");
+    }
+
     let stored_timings = Timings::read_from_file();
 
+    if compare && stored_timings.data.is_empty() {
+        eprintln!("No stored timings to compare against, run `cargo time --store` first.");
+        process::exit(1);
+    }
+
     let days_to_run = day.map_or_else(
         || {
             if run_all {
                 all_days().collect()
+            } else if compare {
+                // only days with stored timings can be compared.
+                stored_timings.data.iter().map(|t| t.day).collect()
             } else {
                 // when the `--all` flag is not set, filter out days that are fully benched.
                 all_days()
@@ -21,7 +52,22 @@ pub fn handle(day: Option<Day>, run_all: bool, store: bool) {
         |day| HashSet::from([day]),
     );
 
-    let timings = run_multi(&days_to_run, true, true).unwrap();
+    let timings = run_multi(
+        &days_to_run,
+        true,
+        Some(budget_ms.map_or(DEFAULT_BENCH_BUDGET, Duration::from_millis)),
+        jobs,
+    )
+    .unwrap();
+
+    let regressions = if compare {
+        let deltas = compare::deltas(&stored_timings, &timings);
+
+        println!();
+        compare::print_table(&deltas, threshold)
+    } else {
+        0
+    };
 
     if store {
         let merged_timings = stored_timings.merge(&timings);
@@ -32,9 +78,15 @@ pub fn handle(day: Option<Day>, run_all: bool, store: bool) {
             Ok(()) => {
                 println!("Stored updated benchmarks.");
             }
-            Err(_) => {
-                eprintln!("Failed to store updated benchmarks.");
+            Err(e) => {
+                eprintln!("Failed to store updated benchmarks: {e}");
             }
         }
     }
+
+    if regressions > 0 {
+        eprintln!();
+        eprintln!("{regressions} part(s) regressed by more than {threshold}%.");
+        process::exit(1);
+    }
 }
@@ -0,0 +1,179 @@
+//! Module that compares fresh timings against the stored ones to catch slowdowns.
+
+use std::time::Duration;
+
+use crate::template::timings::{Timing, Timings};
+use crate::template::{Day, ANSI_BOLD, ANSI_RESET};
+
+/// How much slower a part may get, in percent, before it counts as a regression.
+pub const DEFAULT_THRESHOLD: f64 = 10.0;
+
+/// The change in one part's median duration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub day: Day,
+    pub part: u8,
+    pub stored: Duration,
+    pub current: Duration,
+}
+
+impl Delta {
+    /// The change relative to the stored duration, in percent.
+    pub fn change(&self) -> f64 {
+        let stored = self.stored.as_secs_f64();
+        (self.current.as_secs_f64() - stored) / stored * 100.0
+    }
+
+    pub fn is_regression(&self, threshold: f64) -> bool {
+        self.change() > threshold
+    }
+}
+
+/// Pairs up the parts timed in both `stored` and `current`, in day order.
+pub fn deltas(stored: &Timings, current: &Timings) -> Vec<Delta> {
+    let mut deltas = vec![];
+
+    for timing in &current.data {
+        let Some(old) = stored.data.iter().find(|t| t.day == timing.day) else {
+            continue;
+        };
+
+        for part in [1, 2] {
+            if let (Some(stored), Some(current)) = (median(old, part), median(timing, part)) {
+                if !stored.is_zero() {
+                    deltas.push(Delta {
+                        day: timing.day,
+                        part,
+                        stored,
+                        current,
+                    });
+                }
+            }
+        }
+    }
+
+    deltas.sort_unstable_by_key(|d| (d.day, d.part));
+    deltas
+}
+
+/// The median of a part, falling back to the formatted duration of timings stored before
+/// statistics were recorded.
+fn median(timing: &Timing, part: u8) -> Option<Duration> {
+    let (formatted, stats) = match part {
+        1 => (&timing.part_1, timing.part_1_stats),
+        2 => (&timing.part_2, timing.part_2_stats),
+        _ => return None,
+    };
+
+    stats
+        .map(|s| s.median)
+        .or_else(|| parse_duration(formatted.as_deref()?))
+}
+
+/// Reads a duration printed with `{:.1?}`, such as `74.1µs` or `2.0s`.
+fn parse_duration(s: &str) -> Option<Duration> {
+    // for possible time formats, see: https://github.com/rust-lang/rust/blob/1.64.0/library/core/src/time.rs#L1176-L1200
+    let (value, nanos_per_unit) = if let Some(v) = s.strip_suffix("ns") {
+        (v, 1_f64)
+    } else if let Some(v) = s.strip_suffix("µs") {
+        (v, 1_000_f64)
+    } else if let Some(v) = s.strip_suffix("ms") {
+        (v, 1_000_000_f64)
+    } else {
+        (s.strip_suffix('s')?, 1_000_000_000_f64)
+    };
+
+    let nanos = value.trim().parse::<f64>().ok()? * nanos_per_unit;
+    Some(Duration::from_secs_f64(nanos / 1_000_000_000_f64))
+}
+
+/// Prints a table of the deltas, flagging regressions beyond `threshold` percent.
+/// Returns the number of regressions.
+pub fn print_table(deltas: &[Delta], threshold: f64) -> usize {
+    println!("{ANSI_BOLD}Compared to stored timings{ANSI_RESET} (threshold {threshold}%)");
+    println!();
+    println!(
+        "{:>4} {:>5} {:>12} {:>12} {:>9}",
+        "Day", "Part", "Stored", "Current", "Change"
+    );
+
+    for delta in deltas {
+        let flag = if delta.is_regression(threshold) {
+            format!("  {ANSI_BOLD}✖ regression{ANSI_RESET}")
+        } else {
+            String::new()
+        };
+
+        println!(
+            "{:>4} {:>5} {:>12} {:>12} {:>+8.1}%{flag}",
+            delta.day.into_inner(),
+            delta.part,
+            format!("{:.1?}", delta.stored),
+            format!("{:.1?}", delta.current),
+            delta.change(),
+        );
+    }
+
+    deltas.iter().filter(|d| d.is_regression(threshold)).count()
+}
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use std::time::Duration;
+
+    use super::{deltas, parse_duration};
+    use crate::day;
+    use crate::template::runner::Stats;
+    use crate::template::timings::{Timing, Timings};
+
+    fn timing(day: u8, part_1: Option<&str>, part_2: Option<Duration>) -> Timing {
+        Timing {
+            day: crate::template::Day::new(day).unwrap(),
+            part_1: part_1.map(Into::into),
+            part_2: part_2.map(|d| format!("{d:.1?}")),
+            part_1_stats: None,
+            part_2_stats: part_2.map(|d| Stats::of(&[d])),
+            total_nanos: 0_f64,
+        }
+    }
+
+    #[test]
+    fn parses_formatted_durations() {
+        assert_eq!(parse_duration("74.0ns"), Some(Duration::from_nanos(74)));
+        assert_eq!(parse_duration("1.5µs"), Some(Duration::from_nanos(1500)));
+        assert_eq!(parse_duration("20.0ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_duration("2.0s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration("-"), None);
+    }
+
+    #[test]
+    fn compares_parts_present_in_both() {
+        let stored = Timings {
+            data: vec![
+                timing(1, Some("10.0ms"), Some(Duration::from_millis(20))),
+                timing(2, Some("1.0ms"), None),
+            ],
+        };
+        let current = Timings {
+            data: vec![
+                timing(2, Some("1.05ms"), Some(Duration::from_millis(5))),
+                timing(1, Some("8.0ms"), Some(Duration::from_millis(30))),
+                timing(3, Some("1.0ms"), None),
+            ],
+        };
+
+        let deltas = deltas(&stored, &current);
+
+        assert_eq!(
+            deltas
+                .iter()
+                .map(|d| (d.day, d.part, d.change().round()))
+                .collect::<Vec<_>>(),
+            [(day!(1), 1, -20.0), (day!(1), 2, 50.0), (day!(2), 1, 5.0)]
+        );
+        assert!(!deltas[0].is_regression(10.0));
+        assert!(deltas[1].is_regression(10.0));
+        assert!(!deltas[2].is_regression(10.0));
+        assert!(deltas[2].is_regression(1.0));
+    }
+}
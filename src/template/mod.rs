@@ -2,10 +2,12 @@ use std::{env, fs};
 
 pub mod aoc_cli;
 pub mod commands;
+pub mod registry;
 pub mod runner;
 
 pub use day::*;
 
+mod compare;
 mod day;
 mod readme_benchmarks;
 mod run_multi;
@@ -39,19 +41,35 @@ pub fn read_file_part(folder: &str, day: Day, part: u8) -> String {
 /// Creates the constant `DAY` and sets up the input and runner for each part.
 ///
 /// The optional, second parameter (1 or 2) allows you to only run a single part of the solution.
+/// `visualize 1`, or `visualize 1 2`, marks parts that draw frames. They take an
+/// `Option<&mut FrameSink>` after the input, which `main` builds from `--visualize` and the
+/// in-process runner leaves empty.
 #[macro_export]
 macro_rules! solution {
     ($day:expr) => {
-        $crate::solution!(@impl $day, [part_one, 1] [part_two, 2]);
+        $crate::solution!(@impl $day, [part_one, 1, plain] [part_two, 2, plain]);
     };
     ($day:expr, 1) => {
-        $crate::solution!(@impl $day, [part_one, 1]);
+        $crate::solution!(@impl $day, [part_one, 1, plain]);
     };
     ($day:expr, 2) => {
-        $crate::solution!(@impl $day, [part_two, 2]);
+        $crate::solution!(@impl $day, [part_two, 2, plain]);
+    };
+    ($day:expr, visualize 1) => {
+        $crate::solution!(@impl $day, [part_one, 1, frames] [part_two, 2, plain]);
+    };
+    ($day:expr, visualize 1 2) => {
+        $crate::solution!(@impl $day, [part_one, 1, frames] [part_two, 2, frames]);
+    };
+
+    (@part plain, $func:expr, $sink:expr) => {
+        $func
+    };
+    (@part frames, $func:expr, $sink:expr) => {
+        |input: &str| $func(input, $sink)
     };
 
-    (@impl $day:expr, $( [$func:expr, $part:expr] )*) => {
+    (@impl $day:expr, $( [$func:expr, $part:expr, $mode:ident] )*) => {
         /// The current day.
         const DAY: $crate::template::Day = $crate::day!($day);
 
@@ -59,10 +77,42 @@ macro_rules! solution {
         #[global_allocator]
         static ALLOC: dhat::Alloc = dhat::Alloc;
 
+        #[allow(dead_code)]
         fn main() {
             use $crate::template::runner::*;
             let input = $crate::template::read_file("inputs", DAY);
-            $( run_part($func, &input, DAY, $part); )*
+            #[allow(unused_variables)]
+            let sink = std::cell::RefCell::new($crate::render::FrameSink::from_args());
+            $(
+                // Only the first run draws frames, so `--time` benchmarks the part alone.
+                #[allow(unused_variables)]
+                let first_run = std::cell::Cell::new(true);
+                run_part(
+                    $crate::solution!(
+                        @part $mode,
+                        $func,
+                        sink.borrow_mut().as_mut().filter(|_| first_run.replace(false))
+                    ),
+                    input.as_str(),
+                    DAY,
+                    $part,
+                );
+            )*
         }
+
+        /// This day's parts, for the in-process runner in `src/bin/all.rs`.
+        #[allow(dead_code)]
+        pub const SOLUTION: $crate::template::registry::Solution =
+            $crate::template::registry::Solution {
+                day: DAY,
+                parts: &[$(
+                    ($part, |input| $crate::template::runner::report_part(
+                        $crate::solution!(@part $mode, $func, None),
+                        input,
+                        DAY,
+                        $part,
+                    )),
+                )*],
+            };
     };
 }
@@ -1,7 +1,8 @@
 /// Module that updates the readme me with timing information.
 /// The approach taken is similar to how `aoc-readme-stars` handles this.
-use std::{fs, io};
+use std::{fmt, fs, io};
 
+use crate::template::runner::Stats;
 use crate::template::timings::Timings;
 use crate::template::Day;
 
@@ -13,6 +14,15 @@ pub enum Error {
     IO(io::Error),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parser(reason) => write!(f, "{reason}"),
+            Error::IO(e) => write!(f, "{e}"),
+        }
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
         Error::IO(e)
@@ -51,6 +61,15 @@ fn locate_table(readme: &str) -> Result<TablePosition, Error> {
     Ok(TablePosition { pos_start, pos_end })
 }
 
+/// Shows the median and standard deviation when the part has statistics.
+fn format_cell(duration: Option<String>, stats: Option<Stats>) -> String {
+    match (duration, stats) {
+        (_, Some(stats)) => format!("{:.1?} ± {:.1?}", stats.median, stats.stddev),
+        (Some(duration), None) => duration,
+        (None, None) => "-".into(),
+    }
+}
+
 fn construct_table(prefix: &str, timings: Timings, total_millis: f64) -> String {
     let header = format!("{prefix} Benchmarks");
 
@@ -68,8 +87,8 @@ fn construct_table(prefix: &str, timings: Timings, total_millis: f64) -> String
             "| [Day {}]({}) | `{}` | `{}` |",
             timing.day.into_inner(),
             path,
-            timing.part_1.unwrap_or_else(|| "-".into()),
-            timing.part_2.unwrap_or_else(|| "-".into())
+            format_cell(timing.part_1, timing.part_1_stats),
+            format_cell(timing.part_2, timing.part_2_stats)
         ));
     }
 
@@ -98,7 +117,10 @@ pub fn update(timings: Timings) -> Result<(), Error> {
 
 #[cfg(feature = "test_lib")]
 mod tests {
+    use std::time::Duration;
+
     use super::{update_content, MARKER};
+    use crate::template::runner::Stats;
     use crate::{day, template::timings::Timing, template::timings::Timings};
 
     fn get_mock_timings() -> Timings {
@@ -108,18 +130,24 @@ mod tests {
                     day: day!(1),
                     part_1: Some("10ms".into()),
                     part_2: Some("20ms".into()),
+                    part_1_stats: None,
+                    part_2_stats: None,
                     total_nanos: 3e+10,
                 },
                 Timing {
                     day: day!(2),
                     part_1: Some("30ms".into()),
                     part_2: Some("40ms".into()),
+                    part_1_stats: None,
+                    part_2_stats: None,
                     total_nanos: 7e+10,
                 },
                 Timing {
                     day: day!(4),
                     part_1: Some("40ms".into()),
                     part_2: Some("50ms".into()),
+                    part_1_stats: None,
+                    part_2_stats: None,
                     total_nanos: 9e+10,
                 },
             ],
@@ -179,4 +207,14 @@ mod tests {
         .join("\n");
         assert_eq!(s, expected);
     }
+
+    #[test]
+    fn formats_median_and_spread() {
+        let mut timings = get_mock_timings();
+        timings.data[0].part_2_stats = Some(Stats::of(&[19, 20, 21].map(Duration::from_millis)));
+
+        let mut s = format!("{}\n{}", MARKER, MARKER);
+        update_content(&mut s, timings, 190.0).unwrap();
+        assert!(s.contains("| [Day 1](./src/bin/01.rs) | `10ms` | `20.0ms ± 1.0ms` |"));
+    }
 }
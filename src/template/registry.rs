@@ -0,0 +1,264 @@
+//! Solutions linked into the single `all` binary, so every day can run in one process.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+use std::{env, fs, thread};
+
+use crate::template::runner::{flag_value, free_args, OutputFormat, PartReport, Status};
+use crate::template::timings::{Timing, Timings};
+use crate::template::{Day, ANSI_BOLD, ANSI_ITALIC, ANSI_RESET};
+
+/// Runs one part on the day's input, timed like `run_part`, and reports the outcome.
+pub type PartFn = fn(&str) -> PartReport;
+
+/// A day's parts, registered by the `solution!` macro as the `SOLUTION` constant of each day.
+pub struct Solution {
+    pub day: Day,
+    pub parts: &'static [(u8, PartFn)],
+}
+
+impl Solution {
+    /// Runs each part in turn. A panicking part is reported as failed, so the parts and days
+    /// after it still run.
+    pub fn reports<'a>(&'a self, input: &'a str) -> impl Iterator<Item = PartReport> + 'a {
+        self.parts.iter().map(move |&(part, func)| {
+            catch_unwind(AssertUnwindSafe(|| func(input))).unwrap_or_else(|payload| PartReport {
+                day: self.day,
+                part,
+                answer: None,
+                reason: Some(format!("panicked: {}", panic_message(&*payload))),
+                duration: Duration::ZERO,
+                samples: 0,
+                stats: None,
+                status: Status::Failed,
+            })
+        })
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Runs the days given on the command line, or every registered day if none are, printing them
+/// in day order. Respects `--time`, `--budget` and `--format json` like the single-day binaries,
+/// and runs up to `--jobs N` days at once.
+pub fn run_all(solutions: &[Solution]) {
+    let args = env::args().collect::<Vec<_>>();
+    let format = OutputFormat::from_args();
+    let selected = selected_days(&args);
+    let jobs = flag_value(&args, "--jobs")
+        .and_then(|jobs| jobs.parse::<usize>().ok())
+        .unwrap_or(1);
+
+    let mut solutions = solutions
+        .iter()
+        .filter(|s| selected.is_empty() || selected.contains(&s.day))
+        .collect::<Vec<_>>();
+    solutions.sort_unstable_by_key(|s| s.day);
+
+    let mut timings = Timings::default();
+    let mut print = |i: usize, reports: Option<Box<dyn Iterator<Item = PartReport> + '_>>| {
+        let day = solutions[i].day;
+        if let Some(timing) = print_day(day, format, i == 0, reports) {
+            timings.data.push(timing);
+        }
+    };
+
+    if jobs <= 1 {
+        for (i, solution) in solutions.iter().enumerate() {
+            let input = read_input(solution.day);
+            print(
+                i,
+                input
+                    .as_deref()
+                    .map(|input| Box::new(solution.reports(input)) as _),
+            );
+        }
+    } else {
+        run_parallel(&solutions, jobs, |i, reports| {
+            print(i, reports.map(|r| Box::new(r.into_iter()) as _));
+        });
+    }
+
+    if format == OutputFormat::Human && args.iter().any(|x| x == "--time") {
+        let total_millis = timings.total_millis();
+        println!(
+            "\n{ANSI_BOLD}Total (Run):{ANSI_RESET} {ANSI_ITALIC}{total_millis:.2}ms{ANSI_RESET}"
+        );
+    }
+}
+
+/// Days are free arguments, as opposed to flags and their values such as `--budget 5`.
+fn selected_days(args: &[String]) -> Vec<Day> {
+    free_args(args)
+        .filter_map(|arg| Day::from_str(arg).ok())
+        .collect()
+}
+
+fn read_input(day: Day) -> Option<String> {
+    let path = env::current_dir()
+        .ok()?
+        .join("data")
+        .join("inputs")
+        .join(format!("{day}.txt"));
+
+    fs::read_to_string(path).ok()
+}
+
+/// Prints a day's reports as they arrive, or that it was not solved if there is no input.
+fn print_day(
+    day: Day,
+    format: OutputFormat,
+    is_first: bool,
+    reports: Option<Box<dyn Iterator<Item = PartReport> + '_>>,
+) -> Option<Timing> {
+    if format == OutputFormat::Human {
+        if !is_first {
+            println!();
+        }
+
+        println!("{ANSI_BOLD}Day {day}{ANSI_RESET}");
+        println!("------");
+    }
+
+    let Some(reports) = reports else {
+        if format == OutputFormat::Human {
+            println!("Not solved.");
+        }
+        return None;
+    };
+
+    let reports = reports
+        .inspect(|report| match format {
+            OutputFormat::Human => report.print(),
+            OutputFormat::Json => report.print_json(),
+        })
+        .collect::<Vec<_>>();
+
+    Some(Timing::from_reports(day, &reports))
+}
+
+/// Runs the solutions on up to `jobs` threads, passing each day's reports to `on_day` in the
+/// order of `solutions`, as soon as it and every day before it have finished.
+fn run_parallel(
+    solutions: &[&Solution],
+    jobs: usize,
+    mut on_day: impl FnMut(usize, Option<Vec<PartReport>>),
+) {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(solutions.len()) {
+            let (next, sender) = (&next, sender.clone());
+
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(solution) = solutions.get(i) else {
+                    break;
+                };
+
+                let reports = read_input(solution.day)
+                    .map(|input| solution.reports(&input).collect::<Vec<_>>());
+
+                if sender.send((i, reports)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        drop(sender);
+
+        let mut finished = BTreeMap::new();
+        let mut next_to_print = 0;
+
+        for (i, reports) in receiver {
+            finished.insert(i, reports);
+
+            while let Some(reports) = finished.remove(&next_to_print) {
+                on_day(next_to_print, reports);
+                next_to_print += 1;
+            }
+        }
+    });
+}
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use std::time::Duration;
+
+    use super::{run_parallel, selected_days, Solution};
+
+    use crate::day;
+    use crate::template::runner::{PartReport, Status};
+
+    fn solved(input: &str) -> PartReport {
+        PartReport {
+            day: day!(1),
+            part: 2,
+            answer: Some(input.len().to_string()),
+            reason: None,
+            duration: Duration::from_nanos(10),
+            samples: 1,
+            stats: None,
+            status: Status::Solved,
+        }
+    }
+
+    #[test]
+    fn isolates_panicking_parts() {
+        let solution = Solution {
+            day: day!(1),
+            parts: &[(1, |_| panic!("boom")), (2, solved)],
+        };
+
+        let reports: Vec<_> = solution.reports("abc").collect();
+
+        assert_eq!(reports[0].status, Status::Failed);
+        assert_eq!(reports[0].part, 1);
+        assert_eq!(reports[0].reason.as_deref(), Some("panicked: boom"));
+        assert_eq!(reports[1], solved("abc"));
+    }
+
+    #[test]
+    fn passes_parallel_days_on_in_order() {
+        let solutions = [day!(3), day!(1), day!(2), day!(5), day!(4)].map(|day| Solution {
+            day,
+            parts: &[(2, solved)],
+        });
+        let solutions = solutions.iter().collect::<Vec<_>>();
+
+        let mut order = vec![];
+        run_parallel(&solutions, 3, |i, _| order.push(i));
+
+        assert_eq!(order, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn selects_days_but_not_flag_values() {
+        for budget in 1..=25 {
+            let args = [
+                "all",
+                "--budget",
+                &budget.to_string(),
+                "3",
+                "--time",
+                "--jobs",
+                "4",
+            ]
+            .map(String::from);
+
+            assert_eq!(selected_days(&args), [day!(3)], "--budget {budget}");
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use std::{collections::HashSet, io};
+use std::{collections::HashSet, fmt, io, mem, process, time::Duration, vec};
 
+use crate::template::runner::PartReport;
 use crate::template::{Day, ANSI_BOLD, ANSI_ITALIC, ANSI_RESET};
 
 use super::{
@@ -7,34 +8,30 @@ use super::{
     timings::{Timing, Timings},
 };
 
-pub fn run_multi(days_to_run: &HashSet<Day>, is_release: bool, is_timed: bool) -> Option<Timings> {
-    let mut timings: Vec<Timing> = Vec::with_capacity(days_to_run.len());
-
-    let mut need_space = false;
-
+/// Runs the given days. With a `bench_budget`, each part is benchmarked for that long and the
+/// timings are returned.
+pub fn run_multi(
+    days_to_run: &HashSet<Day>,
+    is_release: bool,
+    bench_budget: Option<Duration>,
+    jobs: usize,
+) -> Option<Timings> {
     // NOTE: use non-duplicate, sorted day values.
-    all_days()
-        .filter(|day| days_to_run.contains(day))
-        .for_each(|day| {
-            if need_space {
-                println!();
-            }
-            need_space = true;
+    let days: Vec<Day> = all_days().filter(|day| days_to_run.contains(day)).collect();
 
-            println!("{ANSI_BOLD}Day {day}{ANSI_RESET}");
-            println!("------");
+    let mut progress = Progress::new(days.clone());
+    let result = child_commands::run_solutions(&days, bench_budget, is_release, jobs, |report| {
+        progress.record(report);
+    });
 
-            let output = child_commands::run_solution(day, is_timed, is_release).unwrap();
+    if let Err(e) = result {
+        eprintln!("Failed to run solutions: {e}");
+        process::exit(1);
+    }
 
-            if output.is_empty() {
-                println!("Not solved.");
-            } else {
-                let val = child_commands::parse_exec_time(&output, day);
-                timings.push(val);
-            }
-        });
+    let timings = progress.finish();
 
-    if is_timed {
+    if bench_budget.is_some() {
         let timings = Timings { data: timings };
         let total_millis = timings.total_millis();
         println!(
@@ -46,57 +43,137 @@ pub fn run_multi(days_to_run: &HashSet<Day>, is_release: bool, is_timed: bool) -
     }
 }
 
+/// Prints reports under a header per day as they arrive, noting days that produced none.
+struct Progress {
+    pending: vec::IntoIter<Day>,
+    current: Option<Day>,
+    reports: Vec<PartReport>,
+    timings: Vec<Timing>,
+}
+
+impl Progress {
+    fn new(days: Vec<Day>) -> Self {
+        Self {
+            pending: days.into_iter(),
+            current: None,
+            reports: vec![],
+            timings: vec![],
+        }
+    }
+
+    fn record(&mut self, report: PartReport) {
+        while self.current != Some(report.day) {
+            let Some(day) = self.pending.next() else {
+                eprintln!("Ignoring report for unexpected day {}.", report.day);
+                return;
+            };
+            self.start_day(day);
+        }
+
+        report.print();
+        self.reports.push(report);
+    }
+
+    fn start_day(&mut self, day: Day) {
+        if self.current.is_some() {
+            self.finish_day();
+            println!();
+        }
+
+        println!("{ANSI_BOLD}Day {day}{ANSI_RESET}");
+        println!("------");
+        self.current = Some(day);
+    }
+
+    fn finish_day(&mut self) {
+        let Some(day) = self.current else {
+            return;
+        };
+
+        if self.reports.is_empty() {
+            println!("Not solved.");
+        } else {
+            let reports = mem::take(&mut self.reports);
+            self.timings.push(Timing::from_reports(day, &reports));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Timing> {
+        while let Some(day) = self.pending.next() {
+            self.start_day(day);
+        }
+
+        self.finish_day();
+        self.timings
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     BrokenPipe,
     IO(io::Error),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BrokenPipe => write!(f, "could not read the output of the solutions."),
+            Error::IO(e) => write!(f, "{e}"),
+        }
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
         Error::IO(e)
     }
 }
 
-#[must_use]
-pub fn get_path_for_bin(day: Day) -> String {
-    format!("./src/bin/{day}.rs")
-}
-
-/// All solutions live in isolated binaries.
-/// This module encapsulates interaction with these binaries, both invoking them as well as parsing the timing output.
+/// All solutions are linked into the `all` binary, see `src/bin/all.rs`.
+/// This module encapsulates interaction with that binary, both invoking it as well as reading the reports it prints.
 pub mod child_commands {
-    use super::{get_path_for_bin, Error};
+    use super::Error;
+    use crate::template::runner::PartReport;
     use crate::template::Day;
     use std::{
         io::{BufRead, BufReader},
-        path::Path,
         process::{Command, Stdio},
         thread,
+        time::Duration,
     };
 
-    /// Run the solution bin for a given day
-    pub fn run_solution(day: Day, is_timed: bool, is_release: bool) -> Result<Vec<String>, Error> {
-        // skip command invocation for days that have not been scaffolded yet.
-        if !Path::new(&get_path_for_bin(day)).exists() {
-            return Ok(vec![]);
-        }
-
-        let day_padded = day.to_string();
-        let mut args = vec!["run", "--quiet", "--bin", &day_padded];
+    /// Run the given days in a single process, passing each report to `on_report` as it arrives.
+    /// Reports arrive in day order, even when running `jobs` days at once.
+    pub fn run_solutions(
+        days: &[Day],
+        bench_budget: Option<Duration>,
+        is_release: bool,
+        jobs: usize,
+        mut on_report: impl FnMut(PartReport),
+    ) -> Result<(), Error> {
+        let mut args = vec!["run", "--quiet", "--bin", "all"];
 
         if is_release {
             args.push("--release");
         }
 
-        if is_timed {
+        args.extend(["--", "--format", "json"]);
+
+        let budget = bench_budget.map(|budget| budget.as_millis().to_string());
+
+        if let Some(budget) = &budget {
             // mirror `--time` flag to child invocations.
-            args.push("--");
-            args.push("--time");
+            args.extend(["--time", "--budget", budget]);
         }
 
+        let jobs = jobs.to_string();
+        args.extend(["--jobs", &jobs]);
+
+        let days: Vec<String> = days.iter().map(ToString::to_string).collect();
+        args.extend(days.iter().map(String::as_str));
+
         // spawn child command with piped stdout/stderr.
-        // forward output to stdout/stderr while grabbing stdout lines.
+        // forward stderr and anything the solutions print themselves, while collecting reports.
 
         let mut cmd = Command::new("cargo")
             .args(&args)
@@ -107,8 +184,6 @@ pub mod child_commands {
         let stdout = BufReader::new(cmd.stdout.take().ok_or(super::Error::BrokenPipe)?);
         let stderr = BufReader::new(cmd.stderr.take().ok_or(super::Error::BrokenPipe)?);
 
-        let mut output = vec![];
-
         let thread = thread::spawn(move || {
             stderr.lines().for_each(|line| {
                 eprintln!("{}", line.unwrap());
@@ -117,75 +192,27 @@ pub mod child_commands {
 
         for line in stdout.lines() {
             let line = line.unwrap();
-            println!("{line}");
-            output.push(line);
+            match parse_report(&line) {
+                Some(report) => on_report(report),
+                None => println!("{line}"),
+            }
         }
 
         thread.join().unwrap();
         cmd.wait()?;
 
-        Ok(output)
+        Ok(())
     }
 
-    pub fn parse_exec_time(output: &[String], day: Day) -> super::Timing {
-        let mut timings = super::Timing {
-            day,
-            part_1: None,
-            part_2: None,
-            total_nanos: 0_f64,
-        };
-
-        output
-            .iter()
-            .filter_map(|l| {
-                if !l.contains(" samples)") {
-                    return None;
-                }
-
-                let Some((timing_str, nanos)) = parse_time(l) else {
-                    eprintln!("Could not parse timings from line: {l}");
-                    return None;
-                };
-
-                let part = l.split(':').next()?;
-                Some((part, timing_str, nanos))
-            })
-            .for_each(|(part, timing_str, nanos)| {
-                if part.contains("Part 1") {
-                    timings.part_1 = Some(timing_str.into());
-                } else if part.contains("Part 2") {
-                    timings.part_2 = Some(timing_str.into());
-                }
-
-                timings.total_nanos += nanos;
-            });
-
-        timings
-    }
-
-    fn parse_to_float(s: &str, postfix: &str) -> Option<f64> {
-        s.split(postfix).next()?.parse().ok()
-    }
+    /// Reads a report line, or `None` for other output from the solution.
+    pub fn parse_report(line: &str) -> Option<PartReport> {
+        if !line.starts_with('{') {
+            return None;
+        }
 
-    fn parse_time(line: &str) -> Option<(&str, f64)> {
-        // for possible time formats, see: https://github.com/rust-lang/rust/blob/1.64.0/library/core/src/time.rs#L1176-L1200
-        let str_timing = line
-            .split(" samples)")
-            .next()?
-            .split('(')
-            .last()?
-            .split('@')
-            .next()?
-            .trim();
-
-        let parsed_timing = match str_timing {
-            s if s.contains("ns") => s.split("ns").next()?.parse::<f64>().ok(),
-            s if s.contains("µs") => parse_to_float(s, "µs").map(|x| x * 1000_f64),
-            s if s.contains("ms") => parse_to_float(s, "ms").map(|x| x * 1_000_000_f64),
-            s => parse_to_float(s, "s").map(|x| x * 1_000_000_000_f64),
-        }?;
-
-        Some((str_timing, parsed_timing))
+        line.parse()
+            .map_err(|e| eprintln!("Could not parse report from line: {line} ({e})"))
+            .ok()
     }
 
     /// copied from: https://github.com/rust-lang/rust/blob/1.64.0/library/std/src/macros.rs#L328-L333
@@ -204,53 +231,93 @@ pub mod child_commands {
 
     #[cfg(feature = "test_lib")]
     mod tests {
-        use super::parse_exec_time;
+        use std::time::Duration;
+
+        use tinyjson::JsonValue;
+
+        use super::parse_report;
 
         use crate::day;
+        use crate::template::runner::{PartReport, Stats, Status};
+        use crate::template::timings::Timing;
+
+        fn report(part: u8, answer: Option<&str>, nanos: u64, status: Status) -> PartReport {
+            PartReport {
+                day: day!(1),
+                part,
+                answer: answer.map(Into::into),
+                reason: None,
+                duration: Duration::from_nanos(nanos),
+                samples: 100,
+                stats: None,
+                status,
+            }
+        }
+
+        fn round_trip(report: &PartReport) -> PartReport {
+            parse_report(&JsonValue::from(report).stringify().unwrap()).unwrap()
+        }
 
         #[test]
         fn parses_execution_times() {
-            let res = parse_exec_time(
-                &[
-                    "Part 1: 0 (74.13ns @ 100000 samples)".into(),
-                    "Part 2: 10 (74.13ms @ 99999 samples)".into(),
-                    "".into(),
-                ],
-                day!(1),
-            );
-            assert_approx_eq!(res.total_nanos, 74130074.13_f64);
-            assert_eq!(res.part_1.unwrap(), "74.13ns");
-            assert_eq!(res.part_2.unwrap(), "74.13ms");
+            let reports = [
+                report(1, Some("0"), 74, Status::Solved),
+                report(2, Some("10"), 74_130_000, Status::Solved),
+            ]
+            .map(|r| round_trip(&r));
+
+            let res = Timing::from_reports(day!(1), &reports);
+            assert_approx_eq!(res.total_nanos, 74130074_f64);
+            assert_eq!(res.part_1.unwrap(), "74.0ns");
+            assert_eq!(res.part_2.unwrap(), "74.1ms");
         }
 
         #[test]
         fn parses_with_patterns_in_input() {
-            let res = parse_exec_time(
-                &[
-                    "Part 1: @ @ @ ( ) ms (2s @ 5 samples)".into(),
-                    "Part 2: 10s (100ms @ 1 samples)".into(),
-                    "".into(),
-                ],
-                day!(1),
+            let original = report(
+                1,
+                Some("@ @ @ ( ) ms\n{\"samples)\"}"),
+                2_000_000_000,
+                Status::Solved,
             );
-            assert_approx_eq!(res.total_nanos, 2100000000_f64);
-            assert_eq!(res.part_1.unwrap(), "2s");
-            assert_eq!(res.part_2.unwrap(), "100ms");
+            let parsed = round_trip(&original);
+            assert_eq!(parsed, original);
+
+            let res = Timing::from_reports(day!(1), &[parsed]);
+            assert_approx_eq!(res.total_nanos, 2000000000_f64);
+            assert_eq!(res.part_1.unwrap(), "2.0s");
         }
 
         #[test]
         fn parses_missing_parts() {
-            let res = parse_exec_time(
-                &[
-                    "Part 1: ✖        ".into(),
-                    "Part 2: ✖        ".into(),
-                    "".into(),
-                ],
-                day!(1),
-            );
+            let mut failed = report(2, None, 10, Status::Failed);
+            failed.reason = Some("no answer".into());
+
+            let reports =
+                [report(1, None, 10, Status::Unsolved), failed.clone()].map(|r| round_trip(&r));
+            assert_eq!(reports[1], failed);
+
+            let res = Timing::from_reports(day!(1), &reports);
             assert_approx_eq!(res.total_nanos, 0_f64);
-            assert_eq!(res.part_1.is_none(), true);
-            assert_eq!(res.part_2.is_none(), true);
+            assert!(res.part_1.is_none());
+            assert!(res.part_2.is_none());
+        }
+
+        #[test]
+        fn keeps_benchmark_statistics() {
+            let mut original = report(2, Some("10"), 2_000, Status::Solved);
+            original.stats = Some(Stats::of(&[1, 2, 3].map(Duration::from_micros)));
+            let parsed = round_trip(&original);
+            assert_eq!(parsed, original);
+
+            let res = Timing::from_reports(day!(1), &[parsed]);
+            assert_eq!(res.part_2_stats, original.stats);
+        }
+
+        #[test]
+        fn skips_other_output() {
+            assert!(parse_report("Part 1: 10").is_none());
+            assert!(parse_report("").is_none());
         }
     }
 }
@@ -1,35 +1,413 @@
 /// Encapsulates code that interacts with solution functions.
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::hint::black_box;
 use std::io::{stdout, Write};
 use std::process::Output;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 use std::{cmp, env, process};
 
+use tinyjson::JsonValue;
+
 use crate::template::ANSI_BOLD;
 use crate::template::{aoc_cli, Day, ANSI_ITALIC, ANSI_RESET};
 
-pub fn run_part<I: Clone, T: Display>(func: impl Fn(I) -> Option<T>, input: I, day: Day, part: u8) {
+/// The value returned by a solution part: either `Option<T>`, or `Result<T, E>` if the
+/// solution can explain why it did not find an answer.
+pub trait PartResult {
+    type Answer: Display;
+
+    /// The answer, or the reason there is none.
+    fn answer(&self) -> Result<&Self::Answer, Option<String>>;
+}
+
+impl<T: Display> PartResult for Option<T> {
+    type Answer = T;
+
+    fn answer(&self) -> Result<&T, Option<String>> {
+        self.as_ref().ok_or(None)
+    }
+}
+
+impl<T: Display, E: Display> PartResult for Result<T, E> {
+    type Answer = T;
+
+    fn answer(&self) -> Result<&T, Option<String>> {
+        self.as_ref().map_err(|e| Some(e.to_string()))
+    }
+}
+
+/// Flags of the solution binaries that take a value. Their values are never free arguments, such
+/// as the days selected by the `all` binary.
+const VALUE_FLAGS: [&str; 6] = [
+    "--budget",
+    "--format",
+    "--frames",
+    "--jobs",
+    "--submit",
+    "--viewport",
+];
+
+/// The value following `flag` in `args`.
+pub fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let index = args.iter().position(|x| x == flag)?;
+    args.get(index + 1).map(String::as_str)
+}
+
+/// The arguments after the program name that are neither flags nor the value of one.
+pub fn free_args(args: &[String]) -> impl Iterator<Item = &str> {
+    args.iter()
+        .enumerate()
+        .skip(1)
+        .filter(|&(i, arg)| !arg.starts_with("--") && !VALUE_FLAGS.contains(&args[i - 1].as_str()))
+        .map(|(_, arg)| arg.as_str())
+}
+
+/// How a solution binary prints its results, selected with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Coloured lines for people, the default.
+    Human,
+    /// One [`PartReport`] JSON object per line, for `run_multi` and other tools.
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = env::args().collect();
+
+        match flag_value(&args, "--format") {
+            Some("json") => Self::Json,
+            _ => Self::Human,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Solved,
+    /// The solution returned `None`.
+    Unsolved,
+    /// The solution returned an error, kept in [`PartReport::reason`].
+    Failed,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Solved => "solved",
+            Status::Unsolved => "unsolved",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+/// A summary of the benchmark samples of a part.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub samples: u128,
+    pub min: Duration,
+    pub median: Duration,
+    pub mean: Duration,
+    /// The 95th percentile, by nearest rank.
+    pub p95: Duration,
+    /// The sample standard deviation.
+    pub stddev: Duration,
+}
+
+impl Stats {
+    /// Summarizes `samples`, which must not be empty.
+    pub fn of(samples: &[Duration]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        let n = sorted.len();
+        let nanos = sorted.iter().map(|d| d.as_secs_f64() * 1e9);
+
+        #[allow(clippy::cast_precision_loss)]
+        let mean = nanos.clone().sum::<f64>() / n as f64;
+        #[allow(clippy::cast_precision_loss)]
+        let variance = if n > 1 {
+            nanos.map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+
+        let median = if n % 2 == 1 {
+            sorted[n / 2]
+        } else {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2
+        };
+
+        Self {
+            samples: n as u128,
+            min: sorted[0],
+            median,
+            mean: Duration::from_secs_f64(mean / 1e9),
+            p95: sorted[(n * 95).div_ceil(100) - 1],
+            stddev: Duration::from_secs_f64(variance.sqrt() / 1e9),
+        }
+    }
+
+    fn durations(&self) -> [(&'static str, Duration); 5] {
+        [
+            ("min", self.min),
+            ("median", self.median),
+            ("mean", self.mean),
+            ("p95", self.p95),
+            ("stddev", self.stddev),
+        ]
+    }
+}
+
+impl From<&Stats> for JsonValue {
+    fn from(value: &Stats) -> Self {
+        let mut map: HashMap<String, JsonValue> = HashMap::new();
+
+        #[allow(clippy::cast_precision_loss)]
+        map.insert("samples".into(), JsonValue::Number(value.samples as f64));
+
+        for (key, duration) in value.durations() {
+            #[allow(clippy::cast_precision_loss)]
+            map.insert(key.into(), JsonValue::Number(duration.as_nanos() as f64));
+        }
+
+        JsonValue::Object(map)
+    }
+}
+
+impl TryFrom<&JsonValue> for Stats {
+    type Error = String;
+
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        let json = value
+            .get::<HashMap<String, JsonValue>>()
+            .ok_or("Expected stats to be a JSON object.")?;
+
+        let number = |key: &str| {
+            json.get(key)
+                .and_then(|v| v.get::<f64>().copied())
+                .ok_or(format!("Expected stats.{key} to be a number."))
+        };
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let duration = |key: &str| number(key).map(|nanos| Duration::from_nanos(nanos as u64));
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        Ok(Stats {
+            samples: number("samples")? as u128,
+            min: duration("min")?,
+            median: duration("median")?,
+            mean: duration("mean")?,
+            p95: duration("p95")?,
+            stddev: duration("stddev")?,
+        })
+    }
+}
+
+/// The outcome of running one part, as printed by `--format json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartReport {
+    pub day: Day,
+    pub part: u8,
+    pub answer: Option<String>,
+    pub reason: Option<String>,
+    /// The median duration of the benchmark, or the duration of the only run.
+    pub duration: Duration,
+    pub samples: u128,
+    /// Present when the part was benchmarked with `--time`.
+    pub stats: Option<Stats>,
+    pub status: Status,
+}
+
+impl PartReport {
+    pub fn new<R: PartResult>(
+        day: Day,
+        part: u8,
+        result: &R,
+        first_run: Duration,
+        stats: Option<Stats>,
+    ) -> Self {
+        let (answer, reason, status) = match result.answer() {
+            Ok(answer) => (Some(answer.to_string()), None, Status::Solved),
+            Err(None) => (None, None, Status::Unsolved),
+            Err(Some(reason)) => (None, Some(reason), Status::Failed),
+        };
+
+        Self {
+            day,
+            part,
+            answer,
+            reason,
+            duration: stats.map_or(first_run, |s| s.median),
+            samples: stats.map_or(1, |s| s.samples),
+            stats,
+            status,
+        }
+    }
+
+    /// Prints the report the way `run_part` prints a finished part.
+    pub fn print(&self) {
+        let answer = self.answer.as_deref().ok_or(self.reason.as_deref());
+        let duration_str = match self.status {
+            Status::Solved => format_duration(&self.duration, self.stats.as_ref()),
+            Status::Unsolved | Status::Failed => String::new(),
+        };
+
+        print_answer(answer, &format!("Part {}", self.part), &duration_str, false);
+    }
+
+    /// Prints the report as a single line of JSON.
+    pub fn print_json(&self) {
+        // tinyjson only fails to stringify non-finite numbers.
+        println!("{}", JsonValue::from(self).stringify().unwrap());
+    }
+}
+
+impl From<&PartReport> for JsonValue {
+    fn from(value: &PartReport) -> Self {
+        let string_or_null =
+            |s: &Option<String>| s.clone().map_or(JsonValue::Null, JsonValue::String);
+
+        let mut map: HashMap<String, JsonValue> = HashMap::new();
+
+        map.insert("day".into(), JsonValue::String(value.day.to_string()));
+        map.insert("part".into(), JsonValue::Number(f64::from(value.part)));
+        map.insert("answer".into(), string_or_null(&value.answer));
+        map.insert("reason".into(), string_or_null(&value.reason));
+        #[allow(clippy::cast_precision_loss)]
+        map.insert(
+            "duration".into(),
+            JsonValue::Number(value.duration.as_nanos() as f64),
+        );
+        #[allow(clippy::cast_precision_loss)]
+        map.insert("samples".into(), JsonValue::Number(value.samples as f64));
+        map.insert(
+            "stats".into(),
+            value
+                .stats
+                .as_ref()
+                .map_or(JsonValue::Null, JsonValue::from),
+        );
+        map.insert(
+            "status".into(),
+            JsonValue::String(value.status.as_str().into()),
+        );
+
+        JsonValue::Object(map)
+    }
+}
+
+impl FromStr for PartReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let json = JsonValue::from_str(s).or(Err("not valid JSON."))?;
+        let json = json
+            .get::<HashMap<String, JsonValue>>()
+            .ok_or("Expected report to be a JSON object.")?;
+
+        let number = |key: &str| {
+            json.get(key)
+                .and_then(|v| v.get::<f64>().copied())
+                .ok_or(format!("Expected report.{key} to be a number."))
+        };
+        let string_or_null = |key: &str| {
+            json.get(key)
+                .map(|v| {
+                    if v.is_null() {
+                        None
+                    } else {
+                        v.get::<String>().cloned()
+                    }
+                })
+                .ok_or(format!("Expected report.{key} to be null or string."))
+        };
+
+        let day = json
+            .get("day")
+            .and_then(|v| v.get::<String>())
+            .and_then(|day| Day::from_str(day).ok())
+            .ok_or("Expected report.day to be a Day struct.")?;
+
+        let status = match json.get("status").and_then(|v| v.get::<String>()) {
+            Some(s) if s == "solved" => Status::Solved,
+            Some(s) if s == "unsolved" => Status::Unsolved,
+            Some(s) if s == "failed" => Status::Failed,
+            _ => return Err("Expected report.status to be solved, unsolved or failed.".into()),
+        };
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        Ok(PartReport {
+            day,
+            part: number("part")? as u8,
+            answer: string_or_null("answer")?,
+            reason: string_or_null("reason")?,
+            duration: Duration::from_nanos(number("duration")? as u64),
+            samples: number("samples")? as u128,
+            stats: match json.get("stats") {
+                None => None,
+                Some(v) if v.is_null() => None,
+                Some(v) => Some(Stats::try_from(v)?),
+            },
+            status,
+        })
+    }
+}
+
+pub fn run_part<I: Clone, R: PartResult>(func: impl Fn(I) -> R, input: I, day: Day, part: u8) {
     let part_str = format!("Part {part}");
+    let format = OutputFormat::from_args();
 
-    let (result, duration, samples) =
-        run_timed(func, input, |result| print_result(result, &part_str, ""));
+    let (result, first_run, stats) = match format {
+        OutputFormat::Human => run_timed(
+            func,
+            input,
+            Some(&|result: &R| print_result(result, &part_str, "")),
+        ),
+        OutputFormat::Json => run_timed(func, input, None),
+    };
 
-    print_result(&result, &part_str, &format_duration(&duration, samples));
+    match format {
+        OutputFormat::Human => {
+            let duration = stats.map_or(first_run, |s| s.median);
+            print_result(
+                &result,
+                &part_str,
+                &format_duration(&duration, stats.as_ref()),
+            );
+        }
+        OutputFormat::Json => {
+            PartReport::new(day, part, &result, first_run, stats).print_json();
+        }
+    }
 
-    if let Some(result) = result {
+    if let Ok(result) = result.answer() {
         submit_result(result, day, part);
     }
 }
 
-/// Run a solution part. The behavior differs depending on whether we are running a release or debug build:
-///  1. in debug, the function is executed once.
-///  2. in release, the function is benched (approx. 1 second of execution time or 10 samples, whatever take longer.)
+/// Runs a solution part like [`run_part`], but returns the report instead of printing it.
+pub fn report_part<I: Clone, R: PartResult>(
+    func: impl Fn(I) -> R,
+    input: I,
+    day: Day,
+    part: u8,
+) -> PartReport {
+    let (result, first_run, stats) = run_timed(func, input, None);
+    PartReport::new(day, part, &result, first_run, stats)
+}
+
+/// Run a solution part once, returning its result and duration. With `--time`, it is then
+/// benchmarked: warmed up for a tenth of the budget, then run for the rest of it, at least 10 and
+/// at most 10,000 times. The budget is [`DEFAULT_BENCH_BUDGET`], or `--budget <ms>`.
+///
+/// Progress is only printed when there is a `hook` to print the intermediate result.
 fn run_timed<I: Clone, T>(
     func: impl Fn(I) -> T,
     input: I,
-    hook: impl Fn(&T),
-) -> (T, Duration, u128) {
+    hook: Option<&dyn Fn(&T)>,
+) -> (T, Duration, Option<Stats>) {
     let timer = Instant::now();
     let result = {
         let input = input.clone();
@@ -41,30 +419,50 @@ fn run_timed<I: Clone, T>(
     };
     let base_time = timer.elapsed();
 
-    hook(&result);
+    if let Some(hook) = hook {
+        hook(&result);
+    }
 
-    let run = if std::env::args().any(|x| x == "--time") {
-        bench(func, input, &base_time)
-    } else {
-        (base_time, 1)
-    };
+    let stats = std::env::args()
+        .any(|x| x == "--time")
+        .then(|| bench(func, input, hook.is_some()));
 
-    (result, run.0, run.1)
+    (result, base_time, stats)
 }
 
-fn bench<I: Clone, T>(func: impl Fn(I) -> T, input: I, base_time: &Duration) -> (Duration, u128) {
-    let mut stdout = stdout();
+/// The time spent benchmarking each part, unless `--budget <ms>` says otherwise.
+pub const DEFAULT_BENCH_BUDGET: Duration = Duration::from_secs(1);
 
-    print!(" > {ANSI_ITALIC}benching{ANSI_RESET}");
-    let _ = stdout.flush();
+/// Reads `--budget <ms>`, the time to spend benchmarking each part.
+fn bench_budget() -> Duration {
+    let args: Vec<String> = env::args().collect();
 
-    let bench_iterations = cmp::min(
-        10000,
-        cmp::max(
-            Duration::from_secs(1).as_nanos() / cmp::max(base_time.as_nanos(), 10),
-            10,
-        ),
-    );
+    flag_value(&args, "--budget")
+        .and_then(|budget| budget.parse().ok())
+        .map_or(DEFAULT_BENCH_BUDGET, Duration::from_millis)
+}
+
+fn bench<I: Clone, T>(func: impl Fn(I) -> T, input: I, show_progress: bool) -> Stats {
+    if show_progress {
+        let mut stdout = stdout();
+        print!(" > {ANSI_ITALIC}benching{ANSI_RESET}");
+        let _ = stdout.flush();
+    }
+
+    let budget = bench_budget();
+
+    // warm up caches and branch predictors, and estimate how long a run takes.
+    let warmup = Instant::now();
+    let mut warmup_runs = 0;
+    while warmup_runs == 0 || warmup.elapsed() < budget / 10 {
+        black_box(func(black_box(input.clone())));
+        warmup_runs += 1;
+    }
+    let estimate = warmup.elapsed() / warmup_runs;
+
+    let bench_iterations = (budget.saturating_sub(warmup.elapsed()).as_nanos()
+        / cmp::max(estimate.as_nanos(), 10))
+    .clamp(10, 10000);
 
     let mut timers: Vec<Duration> = vec![];
 
@@ -76,35 +474,39 @@ fn bench<I: Clone, T>(func: impl Fn(I) -> T, input: I, base_time: &Duration) ->
         timers.push(timer.elapsed());
     }
 
-    (
-        #[allow(clippy::cast_possible_truncation)]
-        Duration::from_nanos(average_duration(&timers) as u64),
-        bench_iterations,
-    )
+    Stats::of(&timers)
 }
 
-fn average_duration(numbers: &[Duration]) -> u128 {
-    numbers
-        .iter()
-        .map(std::time::Duration::as_nanos)
-        .sum::<u128>()
-        / numbers.len() as u128
-}
-
-fn format_duration(duration: &Duration, samples: u128) -> String {
-    if samples == 1 {
-        format!(" ({duration:.1?})")
-    } else {
-        format!(" ({duration:.1?} @ {samples} samples)")
+fn format_duration(duration: &Duration, stats: Option<&Stats>) -> String {
+    match stats {
+        None => format!(" ({duration:.1?})"),
+        Some(stats) => format!(
+            " ({:.1?} ± {:.1?} @ {} samples; min {:.1?}, mean {:.1?}, p95 {:.1?})",
+            stats.median, stats.stddev, stats.samples, stats.min, stats.mean, stats.p95
+        ),
     }
 }
 
-fn print_result<T: Display>(result: &Option<T>, part: &str, duration_str: &str) {
-    let is_intermediate_result = duration_str.is_empty();
+fn print_result<R: PartResult>(result: &R, part: &str, duration_str: &str) {
+    let answer = result.answer().map(ToString::to_string);
 
-    match result {
-        Some(result) => {
-            if result.to_string().contains('\n') {
+    print_answer(
+        answer.as_deref().map_err(Option::as_deref),
+        part,
+        duration_str,
+        duration_str.is_empty(),
+    );
+}
+
+fn print_answer(
+    answer: Result<&str, Option<&str>>,
+    part: &str,
+    duration_str: &str,
+    is_intermediate_result: bool,
+) {
+    match answer {
+        Ok(result) => {
+            if result.contains('\n') {
                 let str = format!("{part}: ▼ {duration_str}");
                 if is_intermediate_result {
                     print!("{str}");
@@ -123,12 +525,15 @@ fn print_result<T: Display>(result: &Option<T>, part: &str, duration_str: &str)
                 }
             }
         }
-        None => {
+        Err(reason) => {
             if is_intermediate_result {
                 print!("{part}: ✖");
             } else {
                 print!("\r");
-                println!("{part}: ✖             ");
+                match reason {
+                    Some(reason) => println!("{part}: ✖ {reason}"),
+                    None => println!("{part}: ✖             "),
+                }
             }
         }
     }
@@ -172,3 +577,40 @@ fn submit_result<T: Display>(
     println!("Submitting result via aoc-cli...");
     Some(aoc_cli::submit(day, part, &result.to_string()))
 }
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use std::time::Duration;
+
+    use tinyjson::JsonValue;
+
+    use super::Stats;
+
+    #[test]
+    fn summarizes_samples() {
+        let samples = [5, 1, 4, 2, 3, 100].map(Duration::from_nanos);
+        let stats = Stats::of(&samples);
+
+        assert_eq!(stats.samples, 6);
+        assert_eq!(stats.min, Duration::from_nanos(1));
+        assert_eq!(stats.median, Duration::from_nanos(3));
+        assert_eq!(stats.mean, Duration::from_nanos(19));
+        assert_eq!(stats.p95, Duration::from_nanos(100));
+        assert_eq!(stats.stddev, Duration::from_nanos(40));
+
+        let single = Stats::of(&[Duration::from_nanos(7)]);
+        assert_eq!(single.median, Duration::from_nanos(7));
+        assert_eq!(single.stddev, Duration::ZERO);
+    }
+
+    #[test]
+    fn round_trips_stats() {
+        let stats = Stats::of(&[10, 20, 30, 40].map(Duration::from_nanos));
+        let json = JsonValue::from(&stats).stringify().unwrap();
+
+        assert_eq!(
+            Stats::try_from(&json.parse::<JsonValue>().unwrap()),
+            Ok(stats)
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Execution traces and per-instruction profiles for the ElfCode [`Computer`](crate::Computer).
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::cfg::Cfg;
+use crate::disassembler::Disassembly;
+use crate::{Instruction, Program, Word};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// `ip=3 mulr 4 2 1 [0, 1, 1, 3, 1, 0] -> [0, 1, 1, 3, 1, 0]`, or
+    /// `ip=1 superinstruction (42 instructions) [...] -> [...]` for a recognized loop.
+    Text,
+    /// One JSON object per line with `ip`, `instruction`, `before` and `after` keys. Recognized
+    /// loops have a `superinstruction` key with the number of instructions they stand in for
+    /// instead of `instruction`.
+    Jsonl,
+}
+
+/// Writes every executed instruction to `writer`. Write errors are ignored so tracing never
+/// changes the outcome of a run.
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+}
+
+fn join<W: Word>(values: &[W], separator: &str) -> String {
+    let values = values.iter().map(ToString::to_string).collect::<Vec<_>>();
+    values.join(separator)
+}
+
+impl Tracer {
+    pub fn new(writer: impl Write + 'static, format: TraceFormat) -> Self {
+        Self {
+            writer: Box::new(writer),
+            format,
+        }
+    }
+
+    pub fn record<W: Word>(
+        &mut self,
+        ip: usize,
+        instruction: &Instruction,
+        before: &[W],
+        after: &[W],
+    ) {
+        let _ = match self.format {
+            TraceFormat::Text => writeln!(
+                self.writer,
+                "ip={ip} {instruction} [{}] -> [{}]",
+                join(before, ", "),
+                join(after, ", ")
+            ),
+            TraceFormat::Jsonl => writeln!(
+                self.writer,
+                "{{\"ip\":{ip},\"instruction\":\"{instruction}\",\"before\":[{}],\"after\":[{}]}}",
+                join(before, ","),
+                join(after, ",")
+            ),
+        };
+    }
+
+    /// Records a superinstruction at `ip` that stood in for `executed` instructions.
+    pub fn record_superinstruction<W: Word>(
+        &mut self,
+        ip: usize,
+        executed: usize,
+        before: &[W],
+        after: &[W],
+    ) {
+        let _ = match self.format {
+            TraceFormat::Text => writeln!(
+                self.writer,
+                "ip={ip} superinstruction ({executed} instructions) [{}] -> [{}]",
+                join(before, ", "),
+                join(after, ", ")
+            ),
+            TraceFormat::Jsonl => writeln!(
+                self.writer,
+                "{{\"ip\":{ip},\"superinstruction\":{executed},\"before\":[{}],\"after\":[{}]}}",
+                join(before, ","),
+                join(after, ",")
+            ),
+        };
+    }
+
+    pub fn flush(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+/// Hit counts per instruction address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub hits: Vec<usize>,
+    /// Instructions stood in for by the superinstruction starting at each address.
+    pub replaced: Vec<usize>,
+}
+
+/// Number of entries listed in each section of [`Profile::report`].
+const REPORT_ENTRIES: usize = 5;
+
+impl Profile {
+    pub fn new(program_len: usize) -> Self {
+        Self {
+            hits: vec![0; program_len],
+            replaced: vec![0; program_len],
+        }
+    }
+
+    pub fn record(&mut self, ip: usize) {
+        self.hits[ip] += 1;
+    }
+
+    pub fn record_superinstruction(&mut self, ip: usize, executed: usize) {
+        self.replaced[ip] += executed;
+    }
+
+    /// Every instruction executed, including those a superinstruction stood in for.
+    pub fn total(&self) -> usize {
+        self.hits.iter().sum::<usize>() + self.replaced.iter().sum::<usize>()
+    }
+
+    /// Addresses ordered from most to least executed, skipping those never executed.
+    pub fn hottest_instructions(&self) -> Vec<(usize, usize)> {
+        let mut hits = self
+            .hits
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, count)| count > 0)
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits
+    }
+
+    /// Natural loops, identified by their header address, ordered by the number of instructions
+    /// executed inside them.
+    pub fn hottest_loops(&self, cfg: &Cfg) -> Vec<(usize, usize)> {
+        let mut loops = cfg
+            .loops()
+            .iter()
+            .map(|l| {
+                let hits = l
+                    .body
+                    .iter()
+                    .flat_map(|&b| cfg.blocks[b].start..cfg.blocks[b].end)
+                    .map(|address| self.hits[address])
+                    .sum::<usize>();
+                (cfg.blocks[l.header].start, hits)
+            })
+            .filter(|&(_, hits)| hits > 0)
+            .collect::<Vec<_>>();
+        loops.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        loops
+    }
+
+    /// Human readable summary of the hottest instructions and loops.
+    pub fn report(&self, ip_register: usize, program: &Program) -> String {
+        let total = self.total().max(1);
+        let percent = |hits: usize| hits as f64 * 100.0 / total as f64;
+        let disassembly = Disassembly::new(ip_register, program);
+        let cfg = Cfg::new(ip_register, program);
+
+        let mut report = format!("{} instructions executed\n", self.total());
+
+        report.push_str("hottest instructions:\n");
+        for (address, hits) in self.hottest_instructions().into_iter().take(REPORT_ENTRIES) {
+            let _ = writeln!(
+                report,
+                "{:<50}{hits:>12} ({:.1}%)",
+                disassembly.lines[address].to_string(),
+                percent(hits)
+            );
+        }
+
+        report.push_str("hottest loops:\n");
+        for (header, hits) in self.hottest_loops(&cfg).into_iter().take(REPORT_ENTRIES) {
+            let _ = writeln!(
+                report,
+                "{:<50}{hits:>12} ({:.1}%)",
+                format!("  loop at L{header}"),
+                percent(hits)
+            );
+        }
+
+        if self.replaced.iter().any(|&replaced| replaced > 0) {
+            report.push_str("superinstructions:\n");
+            for (address, &replaced) in self.replaced.iter().enumerate() {
+                if replaced > 0 {
+                    let _ = writeln!(
+                        report,
+                        "{:<50}{replaced:>12} ({:.1}%)",
+                        format!("  replaced at L{address}"),
+                        percent(replaced)
+                    );
+                }
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{read_file, read_file_part};
+    use crate::{parse_computer_program, Computer};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn traced_lines(format: TraceFormat) -> Vec<String> {
+        let (ip_register, program) =
+            parse_computer_program(&read_file("examples", crate::day!(19))).unwrap();
+        let buffer = SharedBuffer::default();
+        let mut computer =
+            Computer::new(ip_register, program).with_tracer(Tracer::new(buffer.clone(), format));
+
+        computer.run().unwrap();
+
+        let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        output.lines().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn traces_as_text() {
+        let lines = traced_lines(TraceFormat::Text);
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            "ip=0 seti 5 0 1 [0, 0, 0, 0, 0, 0] -> [0, 5, 0, 0, 0, 0]"
+        );
+    }
+
+    #[test]
+    fn traces_as_jsonl() {
+        let lines = traced_lines(TraceFormat::Jsonl);
+
+        assert_eq!(
+            lines[2],
+            "{\"ip\":2,\"instruction\":\"addi 0 1 0\",\"before\":[2,5,6,0,0,0],\"after\":[3,5,6,0,0,0]}"
+        );
+    }
+
+    #[test]
+    fn profiles_hot_loops() {
+        let (ip_register, program) =
+            parse_computer_program(&read_file_part("examples", crate::day!(19), 2)).unwrap();
+        let mut computer = Computer::new(ip_register, program).with_profile();
+
+        computer.run().unwrap();
+
+        let profile = computer.profile.as_ref().unwrap();
+        assert_eq!(profile.total(), computer.instructions_executed);
+
+        let cfg = Cfg::new(ip_register, &computer.program);
+        let loops = profile.hottest_loops(&cfg);
+        assert_eq!(loops[0].0, 2);
+        assert_eq!(loops[1].0, 3);
+
+        let hottest = profile.hottest_instructions()[0].0;
+        assert!((3..=11).contains(&hottest));
+
+        let report = computer.profile_report().unwrap();
+        assert!(report.contains("loop at L3"));
+    }
+
+    #[test]
+    fn records_superinstructions() {
+        let (ip_register, program) = parse_computer_program(
+            "#ip 5\nseti 7 0 1\nseti 6 0 3\nseti 0 0 2\naddr 1 0 0\naddi 2 1 2\neqrr 3 2 4\naddr 4 5 5\nseti 2 0 5",
+        )
+        .unwrap();
+        let buffer = SharedBuffer::default();
+        let mut computer = Computer::new(ip_register, program)
+            .with_idioms()
+            .with_profile()
+            .with_tracer(Tracer::new(buffer.clone(), TraceFormat::Text));
+
+        computer.run().unwrap();
+
+        let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        assert_eq!(
+            output.lines().last(),
+            Some(
+                "ip=2 superinstruction (30 instructions) [0, 7, 0, 6, 0, 2] -> [42, 7, 6, 6, 1, 7]"
+            )
+        );
+
+        let profile = computer.profile.as_ref().unwrap();
+        assert_eq!(profile.total(), computer.instructions_executed);
+        assert!(computer
+            .profile_report()
+            .unwrap()
+            .contains("superinstructions:\n  replaced at L2"));
+    }
+}
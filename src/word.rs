@@ -0,0 +1,138 @@
+//! Register word types for the ElfCode machine.
+
+use std::fmt;
+use std::num::Wrapping;
+
+/// The value held by a single register.
+///
+/// Plain integer types use checked arithmetic and report overflow, [`Wrapping`] types wrap
+/// around silently.
+pub trait Word: Copy + PartialEq + PartialOrd + fmt::Debug + fmt::Display {
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// Converts an immediate operand or instruction address, `None` if it does not fit.
+    fn from_usize(value: usize) -> Option<Self>;
+
+    /// Converts a register value to an instruction address, `None` if it is not one.
+    fn to_usize(self) -> Option<usize>;
+
+    /// `None` on overflow.
+    fn add(self, rhs: Self) -> Option<Self>;
+
+    /// `None` on overflow.
+    fn mul(self, rhs: Self) -> Option<Self>;
+
+    /// `None` when dividing by zero or on overflow.
+    fn div(self, rhs: Self) -> Option<Self>;
+
+    fn bitand(self, rhs: Self) -> Self;
+
+    fn bitor(self, rhs: Self) -> Self;
+}
+
+macro_rules! checked_word {
+    ($($t:ty),*) => {$(
+        impl Word for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn from_usize(value: usize) -> Option<Self> {
+                Self::try_from(value).ok()
+            }
+
+            fn to_usize(self) -> Option<usize> {
+                usize::try_from(self).ok()
+            }
+
+            #[inline]
+            fn add(self, rhs: Self) -> Option<Self> {
+                self.checked_add(rhs)
+            }
+
+            #[inline]
+            fn mul(self, rhs: Self) -> Option<Self> {
+                self.checked_mul(rhs)
+            }
+
+            #[inline]
+            fn div(self, rhs: Self) -> Option<Self> {
+                self.checked_div(rhs)
+            }
+
+            #[inline]
+            fn bitand(self, rhs: Self) -> Self {
+                self & rhs
+            }
+
+            #[inline]
+            fn bitor(self, rhs: Self) -> Self {
+                self | rhs
+            }
+        }
+    )*};
+}
+
+macro_rules! wrapping_word {
+    ($($t:ty),*) => {$(
+        impl Word for Wrapping<$t> {
+            const ZERO: Self = Wrapping(0);
+            const ONE: Self = Wrapping(1);
+
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            fn from_usize(value: usize) -> Option<Self> {
+                Some(Wrapping(value as $t))
+            }
+
+            fn to_usize(self) -> Option<usize> {
+                usize::try_from(self.0).ok()
+            }
+
+            #[inline]
+            fn add(self, rhs: Self) -> Option<Self> {
+                Some(self + rhs)
+            }
+
+            #[inline]
+            fn mul(self, rhs: Self) -> Option<Self> {
+                Some(self * rhs)
+            }
+
+            #[inline]
+            fn div(self, rhs: Self) -> Option<Self> {
+                (rhs.0 != 0).then(|| self / rhs)
+            }
+
+            #[inline]
+            fn bitand(self, rhs: Self) -> Self {
+                self & rhs
+            }
+
+            #[inline]
+            fn bitor(self, rhs: Self) -> Self {
+                self | rhs
+            }
+        }
+    )*};
+}
+
+checked_word!(usize, u32, u64, i64);
+wrapping_word!(u32, u64, i64);
+
+/// Why a single instruction could not be executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// An arithmetic result or immediate does not fit in the register word.
+    Overflow,
+    /// The instruction refers to a register the machine does not have.
+    RegisterOutOfRange(usize),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::Overflow => write!(f, "arithmetic overflow"),
+            Fault::RegisterOutOfRange(r) => write!(f, "register {r} does not exist"),
+        }
+    }
+}